@@ -1,12 +1,20 @@
 #![allow(dead_code, unused_imports)]
 use a3conv::image::OutputImageFormat;
+use a3conv::kind::{detect_kind, FileKind, DETECT_LEN};
+use a3conv::map::export::{CoordinateSystem, ExportOptions, ExportTransform, MapFormat};
+use a3conv::map::{Map, ParseMode, Wdl};
+use a3conv::wrs::{CollisionPolicy, EntryFilter, Extractor, LzssPreset, NamePattern};
 use clap::{Parser, ValueEnum};
 use core::arch;
+use rayon::prelude::*;
 use std::{
     env,
     fs::{self},
+    io::Read,
     path::{Path, PathBuf},
-    result, vec,
+    result,
+    sync::Arc,
+    vec,
 };
 
 #[derive(Parser, Debug)]
@@ -25,6 +33,54 @@ struct Args {
 
     #[arg(short, long, default_value = "false")]
     convert_files: bool,
+
+    /// Also export converted WMP maps in this format
+    #[arg(long, value_enum)]
+    map_format: Option<MapFormat>,
+
+    /// Axes of exported maps
+    #[arg(long, value_enum, default_value = "y-up-right")]
+    coordinate_system: CoordinateSystem,
+
+    /// Exported units per map unit
+    #[arg(long, default_value_t = 1.0)]
+    map_scale: f32,
+
+    /// LZSS variant used by the archives
+    #[arg(long, value_enum, default_value = "acknex3")]
+    lzss: LzssPreset,
+
+    /// Override the number of window size bits of the LZSS preset
+    #[arg(long)]
+    lzss_ei: Option<u32>,
+
+    /// Override the number of match length bits of the LZSS preset
+    #[arg(long)]
+    lzss_ej: Option<u32>,
+
+    /// Override the longest match the LZSS preset stores as literals
+    #[arg(long)]
+    lzss_p: Option<u32>,
+
+    /// Check the archives for corruption instead of extracting them
+    #[arg(long, default_value = "false")]
+    verify: bool,
+
+    /// Only extract matching entries, a glob, `re:<regex>`, `ext:<extension>` or a name
+    #[arg(long)]
+    include: Vec<NamePattern>,
+
+    /// Skip matching entries, same syntax as --include
+    #[arg(long)]
+    exclude: Vec<NamePattern>,
+
+    /// How to handle entries whose names collide with an extracted file
+    #[arg(long, value_enum, default_value = "rename")]
+    collisions: CollisionPolicy,
+
+    /// Number of threads for extraction and conversion, 0 uses every core
+    #[arg(short, long, default_value = "0")]
+    jobs: usize,
 }
 
 #[derive(Debug, clap::Args)]
@@ -47,6 +103,24 @@ enum ExtractMode {
 fn main() {
     let args = Args::parse();
 
+    let mut lzss = args.lzss.params().to_builder();
+    if let Some(ei) = args.lzss_ei {
+        lzss = lzss.ei(ei);
+    }
+    if let Some(ej) = args.lzss_ej {
+        lzss = lzss.ej(ej);
+    }
+    if let Some(p) = args.lzss_p {
+        lzss = lzss.p(p);
+    }
+    let lzss = match lzss.build() {
+        Ok(params) => params,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let extract_mode = match args.input.game_dir.is_some() {
         true => ExtractMode::GameDir,
         false => ExtractMode::Archive,
@@ -81,6 +155,7 @@ fn main() {
                     }
                 })
                 .for_each(|f| results.push(f.file_name().to_str().unwrap().to_string()));
+            results.sort();
 
             results
         }
@@ -91,139 +166,330 @@ fn main() {
 
     println!("Archives: {:?}", archives);
 
-    for archive in archives {
+    if args.verify {
+        let mut corrupt = false;
+
+        for archive in &archives {
+            println!("Verifying archive: {}", archive);
+
+            match a3conv::wrs::verify_archive(archive, &lzss) {
+                Ok(report) => {
+                    println!("{}", report);
+                    corrupt |= !report.is_ok();
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    corrupt = true;
+                }
+            }
+        }
+
+        if corrupt {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let filter = args
+        .include
+        .iter()
+        .cloned()
+        .fold(EntryFilter::new(), EntryFilter::include);
+    let filter = args
+        .exclude
+        .iter()
+        .cloned()
+        .fold(filter, EntryFilter::exclude);
+
+    if args.jobs > 0 {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs)
+            .build_global()
+        {
+            eprintln!("Error: {}", e);
+        }
+    }
+
+    let mut extractor = Extractor::new()
+        .with_params(lzss)
+        .with_filter(filter)
+        .with_collisions(args.collisions);
+
+    // Extract every archive at once, entries are decompressed in parallel
+    let mut extract_jobs = Vec::new();
+    for archive in &archives {
         let archive_name = archive.split('.').next().unwrap();
         let output_directory = format!("{}/{}", output, archive_name);
         let original_directory = format!("{}/original", output_directory);
-        println!("Extracting archive: {}", archive_name);
 
         // Create the output directory if it doesn't exist
         if !Path::new(&original_directory).exists() {
             std::fs::create_dir_all(&original_directory).unwrap();
         }
 
-        // Extract the archive
-        match a3conv::wrs::extract_archive(&archive, &original_directory) {
-            Ok(_) => (),
-            Err(e) => eprintln!("Error: {}", e),
-        }
+        extract_jobs.push((archive.clone(), original_directory));
+    }
 
-        // Convert the extracted files
+    println!("Extracting archives...");
+    let results = extractor.extract_many(&extract_jobs);
+    for ((archive, _), result) in extract_jobs.iter().zip(results) {
+        match result {
+            Ok(file_count) => println!("Extracted {} files from {}", file_count, archive),
+            Err(e) => eprintln!("Error: {}: {}", archive, e),
+        }
+    }
 
-        if args.convert_files {
-            println!("Converting files...");
+    // Convert the extracted files
 
-            let converted_directory = format!("{}/converted", output_directory);
-            let image_dir = format!("{}/images", converted_directory);
-            let sound_dir = format!("{}/sound", converted_directory);
-            let script_dir = format!("{}/script", converted_directory);
+    if args.convert_files {
+        println!("Converting files...");
 
-            let vec = vec![&converted_directory, &image_dir, &sound_dir, &script_dir];
+        let image_format = args.image_format.unwrap_or(OutputImageFormat::Png);
+        let transform = ExportTransform::new(args.coordinate_system).with_scale(args.map_scale);
 
-            for dir in vec {
-                if !Path::new(&dir).exists() {
-                    std::fs::create_dir_all(&dir).unwrap();
-                }
-            }
+        // Gather the files of every archive, sorted so messages come out in a stable order
+        let mut files = Vec::new();
+        for archive in &archives {
+            let archive_name = archive.split('.').next().unwrap();
+            let output_directory = format!("{}/{}", output, archive_name);
+            let original_directory = format!("{}/original", output_directory);
+            let dirs = OutputDirs::create(&output_directory);
 
-            let files = fs::read_dir(&original_directory).unwrap();
-            // Images
-            files
+            let mut archive_files: Vec<PathBuf> = fs::read_dir(&original_directory)
+                .unwrap()
                 .filter_map(Result::ok)
-                .for_each(|f| match f.path().extension() {
-                    Some(e) => {
-                        let file = f.file_name().to_str().unwrap().to_string();
-                        let file = format!("{}/{}", original_directory, file);
-
-                        match e.to_str().unwrap() {
-                            "pcx" | "PCX" => {
-                                let image_format =
-                                    args.image_format.unwrap_or(OutputImageFormat::Png);
-
-                                match a3conv::image::convert_image(
-                                    &PathBuf::from(file),
-                                    &PathBuf::from(&image_dir),
-                                    image_format,
-                                ) {
-                                    Ok(_) => (),
-                                    Err(e) => eprintln!("Image Error: {}", e),
-                                }
-                            }
-                            "wav" | "WAV" => {
-                                let target_file = format!(
-                                    "{}/{}",
-                                    sound_dir,
-                                    f.file_name().to_str().unwrap().to_string()
-                                );
-
-                                let mut source = match std::fs::File::open(&file) {
-                                    Ok(f) => f,
-                                    Err(e) => {
-                                        eprintln!("Error: {}", e);
-                                        return;
-                                    }
-                                };
-                                let mut target = match std::fs::File::create(&target_file) {
-                                    Ok(f) => f,
-                                    Err(e) => {
-                                        eprintln!("Error: {}", e);
-                                        return;
-                                    }
-                                };
-                                match std::io::copy(&mut source, &mut target) {
-                                    Ok(_) => (),
-                                    Err(e) => eprintln!("Error: {}", e),
-                                }
-                            }
-                            "wdl" | "wmp" | "WDL" | "WMP" => {
-                                // TODO: Convert WDL and WMP files, for now just copy them
-
-                                let target_file = format!(
-                                    "{}/{}",
-                                    script_dir,
-                                    f.file_name().to_str().unwrap().to_string()
-                                );
-
-                                let mut source = match std::fs::File::open(&file) {
-                                    Ok(f) => f,
-                                    Err(e) => {
-                                        eprintln!("Error: {}", e);
-                                        return;
-                                    }
-                                };
-                                let mut target = match std::fs::File::create(&target_file) {
-                                    Ok(f) => f,
-                                    Err(e) => {
-                                        eprintln!("Error: {}", e);
-                                        return;
-                                    }
-                                };
-                                match std::io::copy(&mut source, &mut target) {
-                                    Ok(_) => (),
-                                    Err(e) => eprintln!("Error: {}", e),
-                                }
-
-                                /*
-                                let mut map = a3conv::map::Map::default();
-
-                                let path = PathBuf::from(DEBUG_MAP);
-                                match map.parse_wmp(&path) {
-                                    Ok(_) => {
-                                        let output_file = format!("{}/{}.txt", output, map.name());
-                                        println!("Writing to file: {:?}", output_file);
-                                        fs::write(output_file, map.create_vertex_csv()).unwrap();
-                                    }
-                                    Err(e) => eprintln!("Error: {}", e),
-                                }
-                                */
-                            }
-                            _ => {}
-                        }
+                .map(|f| f.path())
+                .collect();
+            archive_files.sort();
+
+            // The maps of an archive share its scripts, so they are only handled once
+            let wdl = args.map_format.and_then(|_| {
+                match prepare_maps(Path::new(&original_directory), &dirs, image_format) {
+                    Ok(wdl) => Some(Arc::new(wdl)),
+                    Err(e) => {
+                        eprintln!("Map Error: {}", e);
+                        None
                     }
-                    None => {}
-                });
+                }
+            });
+
+            files.extend(
+                archive_files
+                    .into_iter()
+                    .map(|f| (f, dirs.clone(), wdl.clone())),
+            );
+        }
+
+        let messages: Vec<Vec<String>> = files
+            .par_iter()
+            .map(|(file, dirs, wdl)| {
+                let map = args.map_format.zip(wdl.as_deref());
+                convert_file(file, dirs, image_format, map, transform)
+            })
+            .collect();
+
+        for message in messages.into_iter().flatten() {
+            eprintln!("{}", message);
+        }
+    }
+
+    if !extractor.manifest().is_empty() {
+        let manifest = format!("{}/manifest.csv", output);
+        match extractor.write_manifest(&manifest) {
+            Ok(_) => println!("Wrote manifest: {}", manifest),
+            Err(e) => eprintln!("Error: {}", e),
         }
     }
 
     println!("Conversion complete!");
 }
+
+/// Directories converted files are sorted into
+#[derive(Debug, Clone)]
+struct OutputDirs {
+    image: String,
+    sound: String,
+    script: String,
+    video: String,
+    map: String,
+}
+
+impl OutputDirs {
+    fn create(output_directory: &str) -> Self {
+        let converted_directory = format!("{}/converted", output_directory);
+        let dirs = Self {
+            image: format!("{}/images", converted_directory),
+            sound: format!("{}/sound", converted_directory),
+            script: format!("{}/script", converted_directory),
+            video: format!("{}/video", converted_directory),
+            map: format!("{}/maps", converted_directory),
+        };
+
+        for dir in [
+            &dirs.image,
+            &dirs.sound,
+            &dirs.script,
+            &dirs.video,
+            &dirs.map,
+        ] {
+            if !Path::new(&dir).exists() {
+                std::fs::create_dir_all(dir).unwrap();
+            }
+        }
+
+        dirs
+    }
+}
+
+/// Convert or copy a single extracted file, returns the messages to print.
+///
+/// Maps are only exported when given a format and the scripts of their archive.
+fn convert_file(
+    file: &Path,
+    dirs: &OutputDirs,
+    image_format: OutputImageFormat,
+    map: Option<(MapFormat, &Wdl)>,
+    transform: ExportTransform,
+) -> Vec<String> {
+    let mut messages = Vec::new();
+
+    // Dispatch on the contents, the extension is only a fallback
+    let kind = match read_head(file) {
+        Ok(head) => detect_kind(&head),
+        Err(e) => {
+            messages.push(format!("Error: {}", e));
+            return messages;
+        }
+    };
+    let extension_kind = FileKind::from_extension(file);
+    if kind != FileKind::Unknown && kind != extension_kind {
+        messages.push(format!(
+            "Warning: {} looks like a {} file",
+            file.display(),
+            kind.to_extension().to_uppercase()
+        ));
+    }
+    let kind = match kind {
+        FileKind::Unknown => extension_kind,
+        kind => kind,
+    };
+
+    let result = match kind {
+        FileKind::Pcx => {
+            match a3conv::image::convert_image(file, Path::new(&dirs.image), image_format) {
+                Ok(_) => None,
+                Err(e) => Some(format!("Image Error: {}", e)),
+            }
+        }
+        // No decoder for these yet, keep them next to the converted files
+        FileKind::Lbm => copy_to(file, &dirs.image),
+        FileKind::Wav | FileKind::Midi => copy_to(file, &dirs.sound),
+        FileKind::Flic => copy_to(file, &dirs.video),
+        FileKind::Wmp => match (copy_to(file, &dirs.script), map) {
+            (None, Some((map_format, wdl))) => {
+                messages.extend(export_map(
+                    file,
+                    wdl,
+                    dirs,
+                    image_format,
+                    map_format,
+                    transform,
+                ));
+                None
+            }
+            (message, _) => message,
+        },
+        // TODO: Convert WDL files, for now just copy them
+        FileKind::Wdl => copy_to(file, &dirs.script),
+        FileKind::Unknown => None,
+    };
+
+    messages.extend(result);
+    messages
+}
+
+/// Parse the WDL scripts extracted next to the maps and convert the textures they cut out of
+/// larger bitmaps
+fn prepare_maps(
+    original_dir: &Path,
+    dirs: &OutputDirs,
+    image_format: OutputImageFormat,
+) -> a3conv::Result<Wdl> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(original_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| FileKind::from_extension(path) == FileKind::Wdl)
+        .collect();
+    scripts.sort();
+
+    let mut wdl = Wdl::new();
+    for script in &scripts {
+        wdl.parse_wdl(script)?;
+    }
+
+    // Cut textures don't depend on the map, convert them before the maps are exported
+    let mut map = Map::default();
+    map.apply_wdl(&wdl, original_dir);
+    map.convert_cut_textures(original_dir, Path::new(&dirs.image), image_format)?;
+
+    Ok(wdl)
+}
+
+/// Export a WMP map next to the converted images, returns the messages to print
+fn export_map(
+    file: &Path,
+    wdl: &Wdl,
+    dirs: &OutputDirs,
+    image_format: OutputImageFormat,
+    map_format: MapFormat,
+    transform: ExportTransform,
+) -> Vec<String> {
+    let options = ExportOptions::new()
+        .with_texture_dir("../images")
+        .with_texture_extension(image_format.to_extension())
+        .with_transform(transform);
+
+    // Texture definitions come from the WDL scripts extracted next to the map
+    let original_dir = file.parent().unwrap_or(Path::new("."));
+
+    let mut messages = Vec::new();
+    let mut map = Map::default();
+    let result = map
+        .parse_wmp_with(file, ParseMode::Lenient)
+        .and_then(|diagnostics| {
+            // Values the engine would read as 0 are loaded the same way, but reported
+            for diagnostic in diagnostics {
+                messages.push(format!("Map Warning: {}", diagnostic));
+            }
+            map.apply_wdl(wdl, original_dir);
+            // Misplaced objects are fixed up as well as possible, exporting still goes ahead
+            for issue in map.place_objects(wdl) {
+                messages.push(format!("Map Warning: {}: {}", file.display(), issue));
+            }
+            map.export(Path::new(&dirs.map), map_format, &options)
+        });
+
+    messages.extend(result.err().map(|e| format!("Map Error: {}", e)));
+    messages
+}
+
+/// Read the start of a file for content detection
+fn read_head(file: &Path) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(DETECT_LEN);
+    fs::File::open(file)?
+        .take(DETECT_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Copy a file into a directory, keeping its name
+fn copy_to(file: &Path, dir: &str) -> Option<String> {
+    let target_file = Path::new(dir).join(file.file_name().unwrap_or_default());
+
+    match fs::copy(file, &target_file) {
+        Ok(_) => None,
+        Err(e) => Some(format!("Error: {}", e)),
+    }
+}
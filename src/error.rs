@@ -0,0 +1,76 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Errors returned by the library
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The archive structure is invalid or an entry doesn't exist
+    Archive(String),
+    /// The LZSS stream or its parameters are invalid
+    Compression(String),
+    /// An entry name pattern couldn't be parsed
+    Pattern(String),
+    /// An entry name can't be used as a file name, it's empty or could escape the output
+    /// directory
+    UnsafeName(String),
+    /// A map file couldn't be parsed, lines and columns start at 1
+    MapParse {
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A map can't be represented in the WMP format
+    MapWrite(String),
+    ImageDecode {
+        file: PathBuf,
+        source: image::ImageError,
+    },
+    ImageEncode {
+        file: PathBuf,
+        source: image::ImageError,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Archive(message) => write!(f, "Archive error: {}", message),
+            Error::Compression(message) => write!(f, "Compression error: {}", message),
+            Error::Pattern(message) => write!(f, "Invalid pattern: {}", message),
+            Error::UnsafeName(message) => write!(f, "Unsafe entry name: {}", message),
+            Error::MapParse {
+                file,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", file.display(), line, column, message),
+            Error::MapWrite(message) => write!(f, "Can't write map: {}", message),
+            Error::ImageDecode { file, source } => {
+                write!(f, "Failed to decode {}: {}", file.display(), source)
+            }
+            Error::ImageEncode { file, source } => {
+                write!(f, "Failed to encode {}: {}", file.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::ImageDecode { source, .. } | Error::ImageEncode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::wrs::{LzssParams, WrsArchive, WrsEntry};
+use crate::{Error, Result};
+
+/// Where a file in the game file system is served from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileSource {
+    /// A loose file in the game directory
+    Loose(PathBuf),
+    /// An entry in one of the mounted archives
+    Archive { archive: PathBuf, entry: WrsEntry },
+}
+
+/// Resolves resources the way the engine does, from loose files and WRS archives.
+///
+/// Names are matched case-insensitively like DOS. Loose files in the game directory take
+/// precedence over archived copies, and archives are searched in mount order, so the first
+/// archive containing a name wins.
+#[derive(Debug, Default)]
+pub struct GameFs {
+    loose: BTreeMap<String, PathBuf>,
+    archives: Vec<(PathBuf, WrsArchive<BufReader<File>>)>,
+    params: LzssParams,
+}
+
+/// Lookup key for a resource name
+fn normalize(name: &str) -> String {
+    name.replace('\\', "/").to_ascii_uppercase()
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("wrs"))
+}
+
+impl GameFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mount a game directory and every archive in it, archives are mounted in name order
+    pub fn open<P: AsRef<Path>>(game_dir: P) -> Result<Self> {
+        Self::open_with_params(game_dir, LzssParams::default())
+    }
+
+    pub fn open_with_params<P: AsRef<Path>>(game_dir: P, params: LzssParams) -> Result<Self> {
+        let mut game_fs = Self {
+            params,
+            ..Default::default()
+        };
+        game_fs.mount_dir(&game_dir)?;
+
+        let mut archives: Vec<PathBuf> = game_fs
+            .loose
+            .values()
+            .filter(|p| is_archive(p))
+            .cloned()
+            .collect();
+        archives.sort_by_key(|p| normalize(&p.to_string_lossy()));
+
+        for archive in archives {
+            game_fs.mount_archive(archive)?;
+        }
+
+        Ok(game_fs)
+    }
+
+    /// Index the loose files in a directory. Subdirectories are not searched, the engine
+    /// only reads from the game directory itself.
+    ///
+    /// Files already mounted win. Names differing only in case resolve to the first one in
+    /// byte order, so the result doesn't depend on the order the OS lists them in.
+    pub fn mount_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+
+        for path in files {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.loose.entry(normalize(&name)).or_insert(path);
+        }
+
+        Ok(())
+    }
+
+    /// Index an archive. Loose files always win over archived copies, and the archive is
+    /// searched after the archives mounted before it.
+    pub fn mount_archive<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref().to_path_buf();
+        let archive = WrsArchive::open(&path)?.with_params(self.params);
+        self.archives.push((path, archive));
+
+        Ok(())
+    }
+
+    /// Paths of the mounted archives in precedence order
+    pub fn archives(&self) -> impl Iterator<Item = &Path> {
+        self.archives.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Find which copy of a file the engine would load
+    pub fn source(&self, name: &str) -> Option<FileSource> {
+        let key = normalize(name);
+
+        if let Some(path) = self.loose.get(&key) {
+            return Some(FileSource::Loose(path.clone()));
+        }
+
+        self.archives.iter().find_map(|(path, archive)| {
+            archive.entry(&key).map(|entry| FileSource::Archive {
+                archive: path.clone(),
+                entry: entry.clone(),
+            })
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.source(name).is_some()
+    }
+
+    /// Every file name visible in the file system, sorted and with its winning source
+    pub fn files(&self) -> BTreeMap<String, FileSource> {
+        let mut files = BTreeMap::new();
+
+        // Insert in reverse precedence so better sources overwrite worse ones
+        for (path, archive) in self.archives.iter().rev() {
+            for entry in archive.entries() {
+                files.insert(
+                    normalize(entry.name()),
+                    FileSource::Archive {
+                        archive: path.clone(),
+                        entry: entry.clone(),
+                    },
+                );
+            }
+        }
+        for (name, path) in &self.loose {
+            files.insert(name.clone(), FileSource::Loose(path.clone()));
+        }
+
+        files
+    }
+
+    /// Read a file, decompressing it if it's archived
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>> {
+        match self.source(name) {
+            Some(FileSource::Loose(path)) => Ok(fs::read(path)?),
+            Some(FileSource::Archive { archive, entry }) => {
+                self.archive_mut(&archive).read_entry(&entry)
+            }
+            None => Err(Error::Archive(format!("File not found: {}", name))),
+        }
+    }
+
+    /// Open a file as a reader, archived files are decompressed as they are read
+    pub fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>> {
+        match self.source(name) {
+            Some(FileSource::Loose(path)) => Ok(Box::new(BufReader::new(File::open(path)?))),
+            Some(FileSource::Archive { archive, entry }) => {
+                Ok(Box::new(self.archive_mut(&archive).entry_reader(&entry)?))
+            }
+            None => Err(Error::Archive(format!("File not found: {}", name))),
+        }
+    }
+
+    fn archive_mut(&mut self, path: &Path) -> &mut WrsArchive<BufReader<File>> {
+        self.archives
+            .iter_mut()
+            .find(|(p, _)| p == path)
+            .map(|(_, archive)| archive)
+            .expect("source() only returns mounted archives")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrs::WrsWriter;
+
+    #[test]
+    fn test_precedence() {
+        let dir = std::env::temp_dir().join(format!("a3conv_gamefs_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut writer = WrsWriter::create(dir.join("A.WRS")).unwrap();
+        writer.add("WALL.PCX", b"archive a").unwrap();
+        writer.add("LEVEL.WMP", b"archive a").unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = WrsWriter::create(dir.join("b.wrs")).unwrap();
+        writer.add("WALL.PCX", b"archive b").unwrap();
+        writer.add("SKY.PCX", b"archive b").unwrap();
+        writer.finish().unwrap();
+
+        fs::write(dir.join("level.wmp"), b"loose").unwrap();
+        fs::write(dir.join("note.txt"), b"lower").unwrap();
+        fs::write(dir.join("NOTE.TXT"), b"upper").unwrap();
+
+        let mut game_fs = GameFs::open(&dir).unwrap();
+        assert_eq!(game_fs.archives().count(), 2);
+
+        assert_eq!(game_fs.read("LEVEL.WMP").unwrap(), b"loose");
+        assert_eq!(game_fs.read("wall.pcx").unwrap(), b"archive a");
+        assert_eq!(game_fs.read("Sky.Pcx").unwrap(), b"archive b");
+        assert!(game_fs.read("MISSING.PCX").is_err());
+        // Case clashes pick the first name in byte order
+        assert_eq!(game_fs.read("note.txt").unwrap(), b"upper");
+
+        let mut contents = Vec::new();
+        game_fs
+            .open_file("wall.pcx")
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"archive a");
+
+        let files = game_fs.files();
+        assert!(matches!(files["LEVEL.WMP"], FileSource::Loose(_)));
+        assert!(matches!(
+            &files["WALL.PCX"],
+            FileSource::Archive { archive, .. } if archive.ends_with("A.WRS")
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
+use crate::{Error, Result};
 use clap::ValueEnum;
-use image::ImageFormat;
+use image::{DynamicImage, ImageFormat, ImageReader};
 use std::ffi::OsStr;
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputImageFormat {
@@ -26,32 +28,62 @@ impl OutputImageFormat {
     }
 }
 
-// TODO: Choose between png and jpeg output
+/// Decode an image, guessing the format from the contents first since extracted files can
+/// have the wrong extension
+fn decode_image(file: &Path) -> Result<DynamicImage> {
+    ImageReader::open(file)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|source| Error::ImageDecode {
+            file: file.to_path_buf(),
+            source,
+        })
+}
+
+/// Write an image as `<name>.<extension>` into a directory
+fn encode_image(
+    image: &DynamicImage,
+    output_dir: &Path,
+    name: &str,
+    output_format: OutputImageFormat,
+) -> Result<()> {
+    let out_path = output_dir.join(format!("{}.{}", name, output_format.to_extension()));
+
+    let mut out = BufWriter::new(File::create(&out_path)?);
+
+    image
+        .write_to(&mut out, output_format.to_image_format())
+        .map_err(|source| Error::ImageEncode {
+            file: out_path,
+            source,
+        })?;
+    out.flush()?;
+
+    Ok(())
+}
+
 pub fn convert_image(
-    file: &PathBuf,
-    output_dir: &PathBuf,
+    file: &Path,
+    output_dir: &Path,
     output_format: OutputImageFormat,
-) -> Result<(), &'static str> {
+) -> Result<()> {
     let name = file
         .file_stem()
         .unwrap_or(OsStr::new("Unknown"))
         .to_string_lossy()
         .to_string();
 
-    let image = image::open(Path::new(&file)).unwrap();
-
-    let out_path = format!(
-        "{}/{}.{}",
-        output_dir.to_str().unwrap(),
-        name,
-        output_format.to_extension()
-    );
-
-    let out = &mut File::create(Path::new(&out_path)).unwrap();
-
-    image
-        .write_to(out, output_format.to_image_format())
-        .unwrap();
+    encode_image(&decode_image(file)?, output_dir, &name, output_format)
+}
 
-    Ok(())
+/// Convert the part of an image at `x, y` with size `dx, dy` into its own image named `name`
+pub fn convert_image_cut(
+    file: &Path,
+    [x, y, dx, dy]: [u32; 4],
+    output_dir: &Path,
+    name: &str,
+    output_format: OutputImageFormat,
+) -> Result<()> {
+    let image = decode_image(file)?.crop_imm(x, y, dx, dy);
+    encode_image(&image, output_dir, name, output_format)
 }
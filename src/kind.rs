@@ -0,0 +1,191 @@
+use std::path::Path;
+
+/// Resource types found in Acknex 3 games
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileKind {
+    Pcx,
+    Wav,
+    Midi,
+    Flic,
+    Lbm,
+    Wdl,
+    Wmp,
+    Unknown,
+}
+
+/// Keywords which only start lines in WMP files
+const WMP_KEYWORDS: &[&str] = &["VERTEX", "PLAYER_START"];
+
+/// Keywords placing objects in WMP files, which also open definitions in WDL files
+const OBJECT_KEYWORDS: &[&str] = &["THING", "ACTOR"];
+
+/// Keywords which start definitions in WDL files
+const WDL_KEYWORDS: &[&str] = &[
+    "BMAP", "TEXTURE", "INCLUDE", "MAPFILE", "PALETTE", "SKILL", "ACTION", "SYNONYM", "STRING",
+    "OVERLAY", "VIDEO", "NEXUS", "PATH", "SOUND", "MUSIC", "FONT", "TEXT", "PANEL", "RULE",
+];
+
+impl FileKind {
+    /// The kind a file name claims to be, going by its extension
+    pub fn from_extension(path: &Path) -> FileKind {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "pcx" => FileKind::Pcx,
+            "wav" => FileKind::Wav,
+            "mid" | "midi" => FileKind::Midi,
+            "fli" | "flc" => FileKind::Flic,
+            "lbm" | "bbm" | "iff" => FileKind::Lbm,
+            "wdl" => FileKind::Wdl,
+            "wmp" => FileKind::Wmp,
+            _ => FileKind::Unknown,
+        }
+    }
+
+    pub fn to_extension(&self) -> &'static str {
+        match self {
+            FileKind::Pcx => "pcx",
+            FileKind::Wav => "wav",
+            FileKind::Midi => "mid",
+            FileKind::Flic => "flc",
+            FileKind::Lbm => "lbm",
+            FileKind::Wdl => "wdl",
+            FileKind::Wmp => "wmp",
+            FileKind::Unknown => "",
+        }
+    }
+}
+
+/// Number of leading bytes `detect_kind` looks at, passing more doesn't change the result
+pub const DETECT_LEN: usize = 4096;
+
+/// Classify a file by its contents instead of its name.
+///
+/// Binary formats are recognised by their magic numbers, WDL and WMP scripts by the keywords
+/// their lines start with.
+pub fn detect_kind(data: &[u8]) -> FileKind {
+    let data = &data[..data.len().min(DETECT_LEN)];
+
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WAVE" {
+        return FileKind::Wav;
+    }
+    if data.starts_with(b"MThd") {
+        return FileKind::Midi;
+    }
+    if data.len() >= 12 && data.starts_with(b"FORM") && matches!(&data[8..12], b"ILBM" | b"PBM ") {
+        return FileKind::Lbm;
+    }
+    // FLI and FLC store their magic after the file size
+    if data.len() >= 6 && matches!(u16::from_le_bytes([data[4], data[5]]), 0xaf11 | 0xaf12) {
+        return FileKind::Flic;
+    }
+    // Manufacturer byte followed by a known version, encoding and bit depth
+    if data.len() >= 128
+        && data[0] == 0x0a
+        && matches!(data[1], 0 | 2 | 3 | 4 | 5)
+        && data[2] <= 1
+        && matches!(data[3], 1 | 2 | 4 | 8)
+    {
+        return FileKind::Pcx;
+    }
+
+    detect_script(data)
+}
+
+fn detect_script(data: &[u8]) -> FileKind {
+    // Scripts are plain text, allowing for a DOS end of file marker
+    let is_text = data
+        .iter()
+        .all(|&b| b.is_ascii_graphic() || b.is_ascii_whitespace() || b == 0x1a);
+    if !is_text || data.is_empty() {
+        return FileKind::Unknown;
+    }
+
+    let text = String::from_utf8_lossy(data);
+    let mut wdl = false;
+
+    for line in text.lines() {
+        let line = line.trim_start();
+        let keyword = line
+            .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+
+        if WMP_KEYWORDS.contains(&keyword.as_str()) {
+            return FileKind::Wmp;
+        }
+        if OBJECT_KEYWORDS.contains(&keyword.as_str()) && is_placed_object(line) {
+            return FileKind::Wmp;
+        }
+        if WDL_KEYWORDS.contains(&keyword.as_str()) || line.contains('{') {
+            wdl = true;
+        }
+    }
+
+    if wdl {
+        FileKind::Wdl
+    } else {
+        FileKind::Unknown
+    }
+}
+
+/// Whether a THING or ACTOR line places an object, `THING name x y angle region;`, rather
+/// than starting a WDL definition
+fn is_placed_object(line: &str) -> bool {
+    if line.contains('{') {
+        return false;
+    }
+    let fields: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|f| !f.is_empty())
+        .collect();
+    fields.len() >= 6 && fields[2..6].iter().all(|f| f.parse::<f32>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_kind() {
+        let mut pcx = vec![0u8; 128];
+        pcx[..4].copy_from_slice(&[0x0a, 5, 1, 8]);
+        assert_eq!(detect_kind(&pcx), FileKind::Pcx);
+
+        assert_eq!(detect_kind(b"RIFF\x24\0\0\0WAVEfmt "), FileKind::Wav);
+        assert_eq!(detect_kind(b"MThd\0\0\0\x06"), FileKind::Midi);
+        assert_eq!(detect_kind(b"\0\x10\0\0\x12\xaf\x40\x01"), FileKind::Flic);
+        assert_eq!(detect_kind(b"FORM\0\0\0\0ILBMBMHD"), FileKind::Lbm);
+
+        let wmp = b"# map\nREGION floor 0 20;\nVERTEX 0 0 0;\nWALL w 0 1 0 0 0 0;\n";
+        assert_eq!(detect_kind(wmp), FileKind::Wmp);
+        let wdl = b"BMAP wall_map, <wall.pcx>;\nREGION floor {\n FLOOR_HGT 0;\n}\n";
+        assert_eq!(detect_kind(wdl), FileKind::Wdl);
+
+        // THING and ACTOR start definitions in WDL files and place objects in WMP files
+        let wdl = b"BMAP lamp_map, <lamp.pcx>;\nTHING lamp {\n FLAGS CEIL_ATTACHED;\n}\nACTOR guard { }\n";
+        assert_eq!(detect_kind(wdl), FileKind::Wdl);
+        assert_eq!(detect_kind(b"THING lamp 10 10 1.5 0;\n"), FileKind::Wmp);
+
+        assert_eq!(detect_kind(b"just some notes"), FileKind::Unknown);
+        assert_eq!(detect_kind(&[0xff, 0x00, 0x13]), FileKind::Unknown);
+        assert_eq!(detect_kind(&[]), FileKind::Unknown);
+    }
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(FileKind::from_extension(Path::new("A.PCX")), FileKind::Pcx);
+        assert_eq!(
+            FileKind::from_extension(Path::new("song.Mid")),
+            FileKind::Midi
+        );
+        assert_eq!(
+            FileKind::from_extension(Path::new("NOEXT")),
+            FileKind::Unknown
+        );
+    }
+}
@@ -1,3 +1,8 @@
+pub mod error;
+pub mod gamefs;
 pub mod image;
+pub mod kind;
 pub mod map;
 pub mod wrs;
+
+pub use error::{Error, Result};
@@ -0,0 +1,410 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use super::ExportOptions;
+use crate::map::Map;
+use crate::Result;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const NEAREST: u32 = 9728;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_JSON_CHUNK: u32 = 0x4E4F_534A;
+const GLB_BIN_CHUNK: u32 = 0x004E_4942;
+
+/// A glTF document and the binary buffer its accessors point into
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gltf {
+    document: Value,
+    buffer: Vec<u8>,
+}
+
+/// Lays out the binary buffer along with its views and accessors
+#[derive(Default)]
+struct BufferBuilder {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BufferBuilder {
+    fn push_vec3(&mut self, values: &[[f32; 3]]) -> usize {
+        let offset = self.data.len();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for value in values {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(value[axis]);
+                max[axis] = max[axis].max(value[axis]);
+                self.data.extend_from_slice(&value[axis].to_le_bytes());
+            }
+        }
+
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.data.len() - offset,
+            "target": ARRAY_BUFFER,
+        }));
+        self.accessors.push(json!({
+            "bufferView": self.views.len() - 1,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_vec2(&mut self, values: &[[f32; 2]]) -> usize {
+        let offset = self.data.len();
+        for value in values.iter().flatten() {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.data.len() - offset,
+            "target": ARRAY_BUFFER,
+        }));
+        self.accessors.push(json!({
+            "bufferView": self.views.len() - 1,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": "VEC2",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, triangles: &[[u32; 3]]) -> usize {
+        let offset = self.data.len();
+        for index in triangles.iter().flatten() {
+            self.data.extend_from_slice(&index.to_le_bytes());
+        }
+
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.data.len() - offset,
+            "target": ELEMENT_ARRAY_BUFFER,
+        }));
+        self.accessors.push(json!({
+            "bufferView": self.views.len() - 1,
+            "componentType": UNSIGNED_INT,
+            "count": triangles.len() * 3,
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+impl Gltf {
+    /// The JSON part of the asset
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+
+    /// Vertex and index data referenced by buffer 0
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Pack the document and buffer into a single binary glTF file
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut json = serde_json::to_vec(&self.document).expect("glTF documents are valid JSON");
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = self.buffer.clone();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_JSON_CHUNK.to_le_bytes());
+        glb.extend_from_slice(&json);
+
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_BIN_CHUNK.to_le_bytes());
+        glb.extend_from_slice(&bin);
+
+        glb
+    }
+}
+
+impl Map {
+    /// Build a glTF asset of the level.
+    ///
+    /// The level is one mesh with a primitive per material, each material referencing its
+    /// converted texture. Objects become named nodes and the regions are listed in the
+    /// level node's `extras`. The buffer has no URI, it's embedded or written alongside.
+    pub fn to_gltf(&self, options: &ExportOptions) -> Gltf {
+        let mut buffer = BufferBuilder::default();
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        let mut textures = Vec::new();
+        let mut images = Vec::new();
+        let transform = options.transform();
+
+        for (material, mesh) in self.material_meshes() {
+            let positions: Vec<[f32; 3]> = mesh
+                .positions()
+                .iter()
+                .map(|p| transform.position(p))
+                .collect();
+            let normals: Vec<[f32; 3]> = mesh
+                .normals()
+                .iter()
+                .map(|n| transform.direction(n))
+                .collect();
+            let triangles: Vec<[u32; 3]> = mesh
+                .triangles()
+                .iter()
+                .map(|&t| transform.triangle(t))
+                .collect();
+            let uvs: Vec<[f32; 2]> = mesh.uvs().iter().map(|uv| [uv.x, uv.y]).collect();
+
+            let position = buffer.push_vec3(&positions);
+            let normal = buffer.push_vec3(&normals);
+            let uv = buffer.push_vec2(&uvs);
+            let indices = buffer.push_indices(&triangles);
+
+            images.push(
+                json!({ "uri": options.texture_uri(self.material_texture(&material).image()) }),
+            );
+            textures.push(json!({ "sampler": 0, "source": images.len() - 1 }));
+            materials.push(json!({
+                "name": material,
+                "pbrMetallicRoughness": {
+                    "baseColorTexture": { "index": textures.len() - 1 },
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+            }));
+            primitives.push(json!({
+                "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv },
+                "indices": indices,
+                "material": materials.len() - 1,
+            }));
+        }
+
+        let regions: Vec<Value> = self
+            .regions()
+            .iter()
+            .enumerate()
+            .map(|(index, region)| {
+                json!({
+                    "index": index,
+                    "name": region.name(),
+                    "floor_height": region.floor_height(),
+                    "ceiling_height": region.ceiling_height(),
+                })
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        let mut meshes = Vec::new();
+        let mut level = json!({
+            "name": self.name(),
+            "extras": { "regions": regions },
+        });
+        if !primitives.is_empty() {
+            meshes.push(json!({ "name": self.name(), "primitives": primitives }));
+            level["mesh"] = json!(0);
+        }
+        nodes.push(level);
+
+        for object in self.objects() {
+            // glTF rotations are written as x, y, z, w like nalgebra stores them
+            let rotation = transform.rotation(object.angle());
+            nodes.push(json!({
+                "name": object.name(),
+                "translation": transform.position(object.position()),
+                "rotation": rotation.coords.as_slice(),
+                "extras": {
+                    "type": object.object_type().keyword(),
+                    "region": object.region().index(),
+                    "angle": transform.angle(object.angle()),
+                },
+            }));
+        }
+
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "a3conv" },
+            "scene": 0,
+            "scenes": [{ "name": self.name(), "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+            "nodes": nodes,
+            "meshes": meshes,
+            "materials": materials,
+            "textures": textures,
+            "images": images,
+            "samplers": [{ "magFilter": NEAREST, "minFilter": NEAREST }],
+        });
+        // The spec doesn't allow empty arrays
+        if let Some(fields) = document.as_object_mut() {
+            fields.retain(|_, value| !value.as_array().is_some_and(|array| array.is_empty()));
+        }
+        if !buffer.data.is_empty() {
+            document["buffers"] = json!([{ "byteLength": buffer.data.len() }]);
+            document["bufferViews"] = json!(buffer.views);
+            document["accessors"] = json!(buffer.accessors);
+        }
+
+        Gltf {
+            document,
+            buffer: buffer.data,
+        }
+    }
+
+    /// Export the level as glTF, `.glb` files are binary and anything else is written as
+    /// JSON with the buffer in a `.bin` file next to it
+    pub fn write_gltf(&self, filename: &Path, options: &ExportOptions) -> Result<()> {
+        let mut gltf = self.to_gltf(options);
+
+        let is_glb = filename
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+        if is_glb {
+            fs::write(filename, gltf.to_glb())?;
+            return Ok(());
+        }
+
+        if !gltf.buffer.is_empty() {
+            let bin_file = filename.with_extension("bin");
+            let uri = bin_file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            gltf.document["buffers"][0]["uri"] = json!(uri);
+            fs::write(bin_file, &gltf.buffer)?;
+        }
+
+        let json =
+            serde_json::to_string_pretty(&gltf.document).expect("glTF documents are valid JSON");
+        fs::write(filename, json)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::square_room;
+    use crate::map::{Object, ObjectType, RegionId, Vertex};
+
+    fn test_map() -> Map {
+        let mut map = square_room();
+        let room = RegionId(0);
+        map.region_mut(room)
+            .unwrap()
+            .set_textures("FLOOR".to_string(), "SKY".to_string());
+        map.add_object(Object::new(
+            ObjectType::PlayerStart,
+            "PLAYER_START".to_string(),
+            Vertex::new(32.0, 16.0, 0.0),
+            std::f32::consts::PI,
+            room,
+        ));
+        map
+    }
+
+    #[test]
+    fn test_gltf() {
+        let gltf = test_map().to_gltf(&ExportOptions::new().with_texture_dir("../images"));
+        let document = gltf.document();
+
+        let materials: Vec<&str> = document["materials"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(materials, ["FLOOR", "SKY", "stone"]);
+        assert_eq!(document["images"][0]["uri"], "../images/FLOOR.png");
+        assert_eq!(
+            document["meshes"][0]["primitives"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // Every accessor fits in the buffer
+        for accessor in document["accessors"].as_array().unwrap() {
+            let view = &document["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+            let end = view["byteOffset"].as_u64().unwrap() + view["byteLength"].as_u64().unwrap();
+            assert!(end as usize <= gltf.buffer().len());
+            let size = match accessor["type"].as_str().unwrap() {
+                "VEC3" => 12,
+                "VEC2" => 8,
+                _ => 4,
+            };
+            assert_eq!(
+                view["byteLength"],
+                accessor["count"].as_u64().unwrap() * size
+            );
+        }
+
+        // The floor is at height 0 and the ceiling at 64, which is Y in glTF
+        let positions: Vec<&Value> = document["meshes"][0]["primitives"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| &document["accessors"][p["attributes"]["POSITION"].as_u64().unwrap() as usize])
+            .collect();
+        let heights = |bound: &str| -> Vec<f64> {
+            positions
+                .iter()
+                .map(|a| a[bound][1].as_f64().unwrap())
+                .collect()
+        };
+        assert_eq!(heights("min").into_iter().reduce(f64::min), Some(0.0));
+        assert_eq!(heights("max").into_iter().reduce(f64::max), Some(64.0));
+
+        let regions = &document["nodes"][0]["extras"]["regions"];
+        assert_eq!(regions[0]["name"], "room");
+
+        let player = &document["nodes"][1];
+        assert_eq!(player["name"], "PLAYER_START");
+        assert_eq!(player["translation"], json!([32.0, 0.0, -16.0]));
+        assert_eq!(player["extras"]["type"], "PLAYER_START");
+    }
+
+    #[test]
+    fn test_empty() {
+        let gltf = Map::new("empty".to_string()).to_gltf(&ExportOptions::new());
+        let document = gltf.document();
+
+        assert!(document.get("meshes").is_none());
+        assert!(document.get("buffers").is_none());
+        assert_eq!(document["nodes"][0]["name"], "empty");
+    }
+
+    #[test]
+    fn test_glb() {
+        let gltf = test_map().to_gltf(&ExportOptions::new());
+        let glb = gltf.to_glb();
+
+        let word = |i: usize| u32::from_le_bytes(glb[i..i + 4].try_into().unwrap());
+        assert_eq!(word(0), GLB_MAGIC);
+        assert_eq!(word(8) as usize, glb.len());
+        assert_eq!(word(16), GLB_JSON_CHUNK);
+
+        let json_len = word(12) as usize;
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        assert_eq!(document["accessors"], gltf.document()["accessors"]);
+        assert_eq!(word(20 + json_len + 4), GLB_BIN_CHUNK);
+        assert_eq!(glb.len() % 4, 0);
+    }
+}
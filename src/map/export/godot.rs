@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use super::{ExportOptions, ExportTransform};
+use crate::map::mesh::SurfaceSource;
+use crate::map::Map;
+use crate::Result;
+
+/// Node names can't contain these characters in Godot
+fn node_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '.' | ':' | '@' | '/' | '"' | '%' => '_',
+            c => c,
+        })
+        .collect();
+
+    match name.is_empty() {
+        true => "Object".to_string(),
+        false => name,
+    }
+}
+
+/// Quote a string for a Godot resource file
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Map {
+    /// Triangles for the level's collision shape, the walls and floors as a flat list of
+    /// corners in Godot space
+    fn collision_faces(&self, transform: &ExportTransform) -> Vec<[f32; 3]> {
+        let mut faces = Vec::new();
+
+        for surface in self.surfaces() {
+            if let SurfaceSource::Ceiling(_) = surface.source() {
+                continue;
+            }
+
+            let mesh = surface.mesh();
+            for &triangle in mesh.triangles() {
+                // Godot treats clockwise faces as the front
+                let [a, b, c] = transform.triangle(triangle);
+                for i in [a, c, b] {
+                    faces.push(transform.position(&mesh.positions()[i as usize]));
+                }
+            }
+        }
+
+        faces
+    }
+
+    /// The level as a Godot 4 scene.
+    ///
+    /// The geometry is loaded from `mesh_file`, an OBJ written by `write_obj` that Godot
+    /// imports as a mesh. Collision is built from the walls and floors, and objects become
+    /// `Marker3D` nodes with their details in metadata.
+    pub fn to_tscn(&self, mesh_file: &str, options: &ExportOptions) -> String {
+        let transform = options.transform();
+
+        let mut tscn = String::new();
+        writeln!(tscn, "[gd_scene load_steps=3 format=3]").unwrap();
+        writeln!(tscn).unwrap();
+        writeln!(
+            tscn,
+            "[ext_resource type=\"ArrayMesh\" path={} id=\"1_mesh\"]",
+            quoted(mesh_file)
+        )
+        .unwrap();
+        writeln!(tscn).unwrap();
+
+        let faces: Vec<String> = self
+            .collision_faces(transform)
+            .iter()
+            .map(|[x, y, z]| format!("{}, {}, {}", x, y, z))
+            .collect();
+        writeln!(
+            tscn,
+            "[sub_resource type=\"ConcavePolygonShape3D\" id=\"ConcavePolygonShape3D_level\"]"
+        )
+        .unwrap();
+        writeln!(tscn, "data = PackedVector3Array({})", faces.join(", ")).unwrap();
+        writeln!(tscn).unwrap();
+
+        let regions: Vec<String> = self.regions().iter().map(|r| quoted(r.name())).collect();
+        writeln!(
+            tscn,
+            "[node name={} type=\"Node3D\"]",
+            quoted(&node_name(self.name()))
+        )
+        .unwrap();
+        writeln!(tscn, "metadata/regions = [{}]", regions.join(", ")).unwrap();
+        writeln!(tscn).unwrap();
+
+        writeln!(
+            tscn,
+            "[node name=\"Level\" type=\"MeshInstance3D\" parent=\".\"]"
+        )
+        .unwrap();
+        writeln!(tscn, "mesh = ExtResource(\"1_mesh\")").unwrap();
+        writeln!(tscn).unwrap();
+
+        writeln!(
+            tscn,
+            "[node name=\"StaticBody3D\" type=\"StaticBody3D\" parent=\".\"]"
+        )
+        .unwrap();
+        writeln!(tscn).unwrap();
+        writeln!(
+            tscn,
+            "[node name=\"CollisionShape3D\" type=\"CollisionShape3D\" parent=\"StaticBody3D\"]"
+        )
+        .unwrap();
+        writeln!(tscn, "shape = SubResource(\"ConcavePolygonShape3D_level\")").unwrap();
+        writeln!(tscn).unwrap();
+
+        writeln!(tscn, "[node name=\"Objects\" type=\"Node3D\" parent=\".\"]").unwrap();
+
+        // Sibling nodes need unique names
+        let mut names = HashSet::new();
+        for object in self.objects() {
+            let base = node_name(object.name());
+            let mut name = base.clone();
+            let mut n = 2;
+            while !names.insert(name.clone()) {
+                name = format!("{}{}", base, n);
+                n += 1;
+            }
+
+            let [x, y, z] = transform.position(object.position());
+            // Godot writes the basis row by row
+            let basis = transform.rotation_matrix(object.angle());
+            let rows: Vec<String> = basis
+                .matrix()
+                .row_iter()
+                .flat_map(|row| {
+                    row.iter()
+                        .map(|v| (v + 0.0).to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+
+            writeln!(tscn).unwrap();
+            writeln!(
+                tscn,
+                "[node name={} type=\"Marker3D\" parent=\"Objects\"]",
+                quoted(&name)
+            )
+            .unwrap();
+            writeln!(
+                tscn,
+                "transform = Transform3D({}, {}, {}, {})",
+                rows.join(", "),
+                x,
+                y,
+                z
+            )
+            .unwrap();
+            writeln!(tscn, "metadata/name = {}", quoted(object.name())).unwrap();
+            writeln!(
+                tscn,
+                "metadata/type = {}",
+                quoted(object.object_type().keyword())
+            )
+            .unwrap();
+            writeln!(tscn, "metadata/angle = {}", transform.angle(object.angle())).unwrap();
+            writeln!(tscn, "metadata/region = {}", object.region()).unwrap();
+        }
+
+        tscn
+    }
+
+    /// Export the level as a Godot scene, with its mesh in an OBJ file next to it
+    pub fn write_tscn(&self, filename: &Path, options: &ExportOptions) -> Result<()> {
+        let obj_file = filename.with_extension("obj");
+        let obj_name = obj_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        self.write_obj(&obj_file, options)?;
+        fs::write(filename, self.to_tscn(&obj_name, options))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::square_room;
+    use crate::map::{Object, ObjectType, RegionId, Vertex};
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_tscn() {
+        let mut map = square_room();
+        let room = RegionId(0);
+        for (name, angle) in [("lamp", 0.0), ("lamp", 0.0), ("PLAYER_START", FRAC_PI_2)] {
+            map.add_object(Object::new(
+                ObjectType::from(name),
+                name.to_string(),
+                Vertex::new(32.0, 16.0, 0.0),
+                angle,
+                room,
+            ));
+        }
+
+        let tscn = map.to_tscn("test.obj", &ExportOptions::new());
+        let lines: Vec<&str> = tscn.lines().collect();
+
+        assert_eq!(lines[0], "[gd_scene load_steps=3 format=3]");
+        assert!(
+            lines.contains(&"[ext_resource type=\"ArrayMesh\" path=\"test.obj\" id=\"1_mesh\"]")
+        );
+        assert!(lines.contains(&"metadata/regions = [\"room\"]"));
+        assert!(lines.contains(&"[node name=\"lamp2\" type=\"Marker3D\" parent=\"Objects\"]"));
+        assert!(lines.contains(&"transform = Transform3D(1, 0, 0, 0, 1, 0, 0, 0, 1, 32, 0, -16)"));
+        assert!(lines.contains(&"metadata/type = \"PLAYER_START\""));
+
+        // Godot reads the basis a row at a time, facing map Y turns a quarter to the left
+        let start: Vec<f32> = lines
+            .iter()
+            .rev()
+            .find_map(|l| l.strip_prefix("transform = Transform3D("))
+            .and_then(|l| l.strip_suffix(')'))
+            .unwrap()
+            .split(", ")
+            .map(|v| v.parse().unwrap())
+            .collect();
+        let expected = [
+            0.0, 0.0, 1.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 32.0, 0.0, -16.0,
+        ];
+        assert!(start
+            .iter()
+            .zip(expected)
+            .all(|(v, e)| (v - e).abs() < 1e-5));
+
+        // The floor and four walls, but not the ceiling
+        let data = lines
+            .iter()
+            .find_map(|l| l.strip_prefix("data = PackedVector3Array("))
+            .unwrap();
+        assert_eq!(data.split(", ").count(), (2 + 4 * 2) * 3 * 3);
+    }
+}
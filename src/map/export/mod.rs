@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use super::mesh::{Mesh, Surface, SurfaceSource};
+use super::{Map, SvgOptions};
+use crate::Result;
+
+pub mod gltf;
+pub mod godot;
+pub mod obj;
+pub mod transform;
+pub use transform::{CoordinateSystem, ExportTransform};
+
+/// File formats maps can be exported to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum MapFormat {
+    /// glTF JSON with a separate .bin buffer
+    Gltf,
+    /// Binary glTF
+    Glb,
+    /// Wavefront OBJ with a .mtl material library
+    Obj,
+    /// Godot 4 scene, with its mesh in an OBJ file
+    Tscn,
+    /// Top-down SVG plan of the level
+    Svg,
+}
+
+impl MapFormat {
+    pub fn to_extension(&self) -> &'static str {
+        match self {
+            MapFormat::Gltf => "gltf",
+            MapFormat::Glb => "glb",
+            MapFormat::Obj => "obj",
+            MapFormat::Tscn => "tscn",
+            MapFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Settings shared by the map exporters
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    texture_dir: String,
+    texture_extension: String,
+    transform: ExportTransform,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            texture_dir: "images".to_string(),
+            texture_extension: "png".to_string(),
+            transform: ExportTransform::default(),
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory of the converted textures, relative to the exported file
+    pub fn with_texture_dir(mut self, texture_dir: &str) -> Self {
+        self.texture_dir = texture_dir.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Extension of the converted textures
+    pub fn with_texture_extension(mut self, texture_extension: &str) -> Self {
+        self.texture_extension = texture_extension.trim_start_matches('.').to_string();
+        self
+    }
+
+    /// Axes and units of the exported level
+    pub fn with_transform(mut self, transform: ExportTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn transform(&self) -> &ExportTransform {
+        &self.transform
+    }
+
+    /// Relative path of the image used by a material
+    pub fn texture_uri(&self, material: &str) -> String {
+        let file = format!("{}.{}", material, self.texture_extension);
+        match self.texture_dir.as_str() {
+            "" => file,
+            dir => format!("{}/{}", dir, file),
+        }
+    }
+}
+
+impl Map {
+    /// Export the map into a directory as `<name>.<extension>`
+    pub fn export(
+        &self,
+        output_dir: &Path,
+        format: MapFormat,
+        options: &ExportOptions,
+    ) -> Result<()> {
+        let filename = output_dir.join(format!("{}.{}", self.name(), format.to_extension()));
+        match format {
+            MapFormat::Gltf | MapFormat::Glb => self.write_gltf(&filename, options),
+            MapFormat::Obj => self.write_obj(&filename, options),
+            MapFormat::Tscn => self.write_tscn(&filename, options),
+            MapFormat::Svg => self.write_svg(&filename, &SvgOptions::new()),
+        }
+    }
+
+    /// Name of the material a surface is drawn with.
+    ///
+    /// This is the texture read from the WDL file, or the name of the wall or region
+    /// definition when no texture has been assigned.
+    pub fn surface_material(&self, surface: &Surface) -> String {
+        self.source_material(surface.source())
+    }
+
+    /// Name of the material a part of the level is drawn with, see `surface_material`
+    pub fn source_material(&self, source: SurfaceSource) -> String {
+        let material = match source {
+            SurfaceSource::Floor(id) => self.region(id).map(|r| (r.floor_texture(), r.name())),
+            SurfaceSource::Ceiling(id) => self.region(id).map(|r| (r.ceiling_texture(), r.name())),
+            SurfaceSource::Wall { wall, .. } => {
+                self.wall(wall).map(|w| (w.wall_texture(), w.name()))
+            }
+        };
+
+        match material {
+            Some(("", name)) => name.to_string(),
+            Some((texture, _)) => texture.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// The level geometry merged into one mesh per material, sorted by material name
+    pub fn material_meshes(&self) -> BTreeMap<String, Mesh> {
+        let mut meshes: BTreeMap<String, Mesh> = BTreeMap::new();
+        for surface in self.surfaces() {
+            meshes
+                .entry(self.surface_material(&surface))
+                .or_default()
+                .append(surface.mesh());
+        }
+        meshes
+    }
+}
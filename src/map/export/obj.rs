@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use super::ExportOptions;
+use crate::map::mesh::Mesh;
+use crate::map::{Map, RegionId};
+use crate::Result;
+
+impl Map {
+    /// Name of the OBJ group holding a region's geometry
+    fn obj_group(&self, region: RegionId) -> String {
+        match self.region(region).map(|r| r.name()) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("region_{}", region),
+        }
+    }
+
+    /// The level as Wavefront OBJ, one group per region and a material per texture.
+    ///
+    /// `mtl_file` is the name of the material library the OBJ refers to.
+    pub fn to_obj(&self, mtl_file: &str, options: &ExportOptions) -> String {
+        let transform = options.transform();
+
+        // Group the surfaces by region and material so each group is written in one go
+        let mut groups: BTreeMap<RegionId, BTreeMap<String, Mesh>> = BTreeMap::new();
+        for surface in self.surfaces() {
+            groups
+                .entry(surface.region())
+                .or_default()
+                .entry(self.surface_material(&surface))
+                .or_default()
+                .append(surface.mesh());
+        }
+
+        let mut obj = String::new();
+        writeln!(obj, "# {}", self.name()).unwrap();
+        writeln!(obj, "mtllib {}", mtl_file).unwrap();
+
+        // OBJ indices are global and start at 1
+        let mut first = 1;
+        for (region, materials) in groups {
+            writeln!(obj, "g {}", self.obj_group(region)).unwrap();
+
+            for (material, mesh) in materials {
+                writeln!(obj, "usemtl {}", material).unwrap();
+
+                for position in mesh.positions() {
+                    let [x, y, z] = transform.position(position);
+                    writeln!(obj, "v {} {} {}", x, y, z).unwrap();
+                }
+                // OBJ textures start at the bottom left
+                for uv in mesh.uvs() {
+                    writeln!(obj, "vt {} {}", uv.x, 1.0 - uv.y).unwrap();
+                }
+                for normal in mesh.normals() {
+                    let [x, y, z] = transform.direction(normal);
+                    writeln!(obj, "vn {} {} {}", x, y, z).unwrap();
+                }
+                for triangle in mesh.triangles() {
+                    let [a, b, c] = transform.triangle(*triangle).map(|i| i as usize + first);
+                    writeln!(obj, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c).unwrap();
+                }
+
+                first += mesh.positions().len();
+            }
+        }
+
+        obj
+    }
+
+    /// The material library for `to_obj`, each material showing its converted texture
+    pub fn to_mtl(&self, options: &ExportOptions) -> String {
+        let materials: BTreeSet<String> = self
+            .surfaces()
+            .iter()
+            .map(|surface| self.surface_material(surface))
+            .collect();
+
+        let mut mtl = String::new();
+        for material in materials {
+            writeln!(mtl, "newmtl {}", material).unwrap();
+            writeln!(mtl, "Ka 1 1 1").unwrap();
+            writeln!(mtl, "Kd 1 1 1").unwrap();
+            writeln!(mtl, "Ks 0 0 0").unwrap();
+            writeln!(mtl, "illum 1").unwrap();
+            writeln!(
+                mtl,
+                "map_Kd {}",
+                options.texture_uri(self.material_texture(&material).image())
+            )
+            .unwrap();
+            writeln!(mtl).unwrap();
+        }
+
+        mtl
+    }
+
+    /// Export the level as OBJ, with the material library next to it in a `.mtl` file
+    pub fn write_obj(&self, filename: &Path, options: &ExportOptions) -> Result<()> {
+        let mtl_file = filename.with_extension("mtl");
+        let mtl_name = mtl_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        fs::write(&mtl_file, self.to_mtl(options))?;
+        fs::write(filename, self.to_obj(&mtl_name, options))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::square_room;
+
+    #[test]
+    fn test_obj() {
+        let map = square_room();
+
+        let obj = map.to_obj("test.mtl", &ExportOptions::new());
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(
+            &lines[..4],
+            ["# test", "mtllib test.mtl", "g room", "usemtl room"]
+        );
+        assert!(lines.contains(&"usemtl stone"));
+
+        // Floor, ceiling and four walls
+        let count = |prefix: &str| lines.iter().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(count("v "), 4 + 4 + 4 * 4);
+        assert_eq!(count("vt "), count("v "));
+        assert_eq!(count("f "), 2 + 2 + 4 * 2);
+        assert!(lines.contains(&"f 4/4/4 1/1/1 2/2/2"));
+
+        // The floor at height 0 maps to Y 0 and map Y to -Z
+        assert!(lines.contains(&"v 64 0 -64"));
+
+        let last = lines.last().unwrap();
+        let max_index = last[2..]
+            .split(' ')
+            .map(|v| v.split('/').next().unwrap().parse::<usize>().unwrap())
+            .max()
+            .unwrap();
+        assert_eq!(max_index, count("v "));
+
+        let mtl = map.to_mtl(&ExportOptions::new().with_texture_dir("textures"));
+        assert!(mtl.starts_with("newmtl room\n"));
+        assert!(mtl.contains("map_Kd textures/room.png\n"));
+    }
+}
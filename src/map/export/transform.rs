@@ -0,0 +1,164 @@
+use clap::ValueEnum;
+use nalgebra::{Rotation3, Unit, UnitQuaternion, Vector3};
+
+/// Axis conventions of the engine a map is exported for.
+///
+/// Map space has X and Y on the plan, with Y pointing forward, and Z up.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum CoordinateSystem {
+    /// Y up, right-handed with -Z forward, used by glTF and Godot
+    #[default]
+    YUpRight,
+    /// Y up, left-handed with Z forward, used by Unity
+    YUpLeft,
+    /// Z up, left-handed with X forward, used by Unreal
+    ZUpLeft,
+    /// Z up, right-handed, the map's own axes
+    ZUpRight,
+}
+
+impl CoordinateSystem {
+    /// Where the map's X, Y and Z axes end up
+    fn swizzle(&self, v: &Vector3<f32>) -> Vector3<f32> {
+        match self {
+            CoordinateSystem::YUpRight => Vector3::new(v.x, v.z, -v.y),
+            CoordinateSystem::YUpLeft => Vector3::new(v.x, v.z, v.y),
+            CoordinateSystem::ZUpLeft => Vector3::new(v.y, v.x, v.z),
+            CoordinateSystem::ZUpRight => *v,
+        }
+    }
+
+    /// The up axis of the system
+    pub fn up(&self) -> Vector3<f32> {
+        match self {
+            CoordinateSystem::YUpRight | CoordinateSystem::YUpLeft => Vector3::y(),
+            CoordinateSystem::ZUpLeft | CoordinateSystem::ZUpRight => Vector3::z(),
+        }
+    }
+
+    /// Whether converting from map space mirrors the level
+    pub fn is_mirrored(&self) -> bool {
+        matches!(self, CoordinateSystem::YUpLeft | CoordinateSystem::ZUpLeft)
+    }
+}
+
+/// Conversion from map space to the space of an exported file
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportTransform {
+    system: CoordinateSystem,
+    scale: f32,
+}
+
+impl Default for ExportTransform {
+    fn default() -> Self {
+        Self {
+            system: CoordinateSystem::default(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl ExportTransform {
+    pub fn new(system: CoordinateSystem) -> Self {
+        Self {
+            system,
+            ..Default::default()
+        }
+    }
+
+    /// Exported units per map unit
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn system(&self) -> CoordinateSystem {
+        self.system
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Convert a position, applying the unit scale
+    pub fn position(&self, v: &Vector3<f32>) -> [f32; 3] {
+        // Adding 0 turns -0 into 0 so it isn't written out
+        (self.system.swizzle(v) * self.scale)
+            .map(|c| c + 0.0)
+            .into()
+    }
+
+    /// Convert a direction such as a normal, which isn't scaled
+    pub fn direction(&self, v: &Vector3<f32>) -> [f32; 3] {
+        self.system.swizzle(v).map(|c| c + 0.0).into()
+    }
+
+    /// Reorder a triangle so it keeps facing the same way after a handedness flip
+    pub fn triangle(&self, [a, b, c]: [u32; 3]) -> [u32; 3] {
+        match self.system.is_mirrored() {
+            true => [a, c, b],
+            false => [a, b, c],
+        }
+    }
+
+    /// Convert an object angle into a turn around the up axis of the exported space.
+    ///
+    /// Map angles turn from X towards Y. The result turns the exported X axis to face the
+    /// same way the object does.
+    pub fn angle(&self, angle: f32) -> f32 {
+        let facing = self
+            .system
+            .swizzle(&Vector3::new(angle.cos(), angle.sin(), 0.0));
+        match self.system.up() == Vector3::y() {
+            // Turning around Y takes X towards -Z
+            true => (-facing.z).atan2(facing.x),
+            false => facing.y.atan2(facing.x),
+        }
+    }
+
+    /// The rotation of an object with the given map angle
+    pub fn rotation(&self, angle: f32) -> UnitQuaternion<f32> {
+        let axis = Unit::new_normalize(self.system.up());
+        UnitQuaternion::from_axis_angle(&axis, self.angle(angle))
+    }
+
+    /// The rotation of an object as a matrix
+    pub fn rotation_matrix(&self, angle: f32) -> Rotation3<f32> {
+        self.rotation(angle).to_rotation_matrix()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_transform() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        let y_up = ExportTransform::default();
+        assert_eq!(y_up.position(&v), [1.0, 3.0, -2.0]);
+        assert_eq!(y_up.triangle([0, 1, 2]), [0, 1, 2]);
+
+        let unreal = ExportTransform::new(CoordinateSystem::ZUpLeft).with_scale(2.5);
+        assert_eq!(unreal.position(&v), [5.0, 2.5, 7.5]);
+        assert_eq!(unreal.direction(&v), [2.0, 1.0, 3.0]);
+        assert_eq!(unreal.triangle([0, 1, 2]), [0, 2, 1]);
+
+        // An object facing along the map's Y axis, which is forward
+        for (system, expected) in [
+            (CoordinateSystem::YUpRight, FRAC_PI_2),
+            (CoordinateSystem::YUpLeft, -FRAC_PI_2),
+            (CoordinateSystem::ZUpLeft, 0.0),
+            (CoordinateSystem::ZUpRight, FRAC_PI_2),
+        ] {
+            let transform = ExportTransform::new(system);
+            assert!((transform.angle(FRAC_PI_2) - expected).abs() < 1e-6);
+
+            // Rotating the exported X axis gives the object's facing
+            let facing = transform.direction(&Vector3::y());
+            let rotated = transform.rotation(FRAC_PI_2) * Vector3::x();
+            assert!((rotated - Vector3::from(facing)).norm() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use nalgebra::Vector2;
+
+use super::{Map, RegionId, VertexId, WallId};
+
+/// A position on the map plan
+pub type Point = Vector2<f32>;
+
+/// Twice the signed area of a polygon, positive when counter-clockwise
+fn signed_area2(points: &[Point]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum()
+}
+
+/// Even-odd test of a point against a polygon
+pub fn point_in_polygon(point: &Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let n = polygon.len();
+    for i in 0..n {
+        let (a, b) = (polygon[i], polygon[(i + n - 1) % n]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// A closed chain of walls around a region
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryLoop {
+    vertices: Vec<VertexId>,
+    walls: Vec<WallId>,
+    points: Vec<Point>,
+}
+
+impl BoundaryLoop {
+    /// Vertices in order, the last one connects back to the first
+    pub fn vertices(&self) -> &[VertexId] {
+        &self.vertices
+    }
+
+    /// Walls in order, wall `i` runs from vertex `i` to vertex `i + 1`
+    pub fn walls(&self) -> &[WallId] {
+        &self.walls
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Area of the loop, positive when counter-clockwise
+    pub fn signed_area(&self) -> f32 {
+        signed_area2(&self.points) / 2.0
+    }
+
+    pub fn is_clockwise(&self) -> bool {
+        self.signed_area() < 0.0
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        point_in_polygon(point, &self.points)
+    }
+
+    fn reverse(&mut self) {
+        // Keep the first vertex so wall i stays between vertices i and i + 1
+        self.walls.reverse();
+        self.vertices.reverse();
+        self.points.reverse();
+        self.vertices.rotate_right(1);
+        self.points.rotate_right(1);
+    }
+
+    /// A point strictly inside the loop, used for nesting tests.
+    ///
+    /// Steps off the middle of an edge towards the inside, a point on the outline itself
+    /// would count as inside or outside of a loop sharing that edge depending on rounding.
+    fn sample_point(&self) -> Point {
+        let n = self.points.len();
+        let inward = if self.is_clockwise() { -1.0 } else { 1.0 };
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            let edge = b - a;
+            // Left of the edge is inside for counter-clockwise loops
+            let normal = Point::new(-edge.y, edge.x) * inward;
+            for step in [0.01, 0.001] {
+                let point = (a + b) / 2.0 + normal * step;
+                if self.contains(&point) {
+                    return point;
+                }
+            }
+        }
+        (self.points[0] + self.points[1 % n]) / 2.0
+    }
+}
+
+/// An outer loop and the holes cut out of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    outer: BoundaryLoop,
+    holes: Vec<BoundaryLoop>,
+}
+
+impl Polygon {
+    /// The outline, counter-clockwise
+    pub fn outer(&self) -> &BoundaryLoop {
+        &self.outer
+    }
+
+    /// Pillars and inner regions, clockwise
+    pub fn holes(&self) -> &[BoundaryLoop] {
+        &self.holes
+    }
+
+    /// The outline's points followed by the points of each hole
+    pub fn points(&self) -> Vec<Point> {
+        let holes = self.holes.iter().flat_map(|h| h.points.iter());
+        self.outer.points.iter().chain(holes).copied().collect()
+    }
+
+    pub fn area(&self) -> f32 {
+        self.outer.signed_area() + self.holes.iter().map(|h| h.signed_area()).sum::<f32>()
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        self.outer.contains(point) && !self.holes.iter().any(|h| h.contains(point))
+    }
+}
+
+/// The floor plan of a region, rebuilt from the walls bordering it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionOutline {
+    region: RegionId,
+    polygons: Vec<Polygon>,
+    open_walls: Vec<WallId>,
+}
+
+impl RegionOutline {
+    pub fn region(&self) -> RegionId {
+        self.region
+    }
+
+    /// The separate pieces of the region, largest first
+    pub fn polygons(&self) -> &[Polygon] {
+        &self.polygons
+    }
+
+    /// Walls which don't form part of a closed loop
+    pub fn open_walls(&self) -> &[WallId] {
+        &self.open_walls
+    }
+
+    /// Whether every wall of the region is part of a closed loop
+    pub fn is_closed(&self) -> bool {
+        self.open_walls.is_empty() && !self.polygons.is_empty()
+    }
+
+    pub fn area(&self) -> f32 {
+        self.polygons.iter().map(|p| p.area()).sum()
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        self.polygons.iter().any(|p| p.contains(point))
+    }
+}
+
+/// An edge of the region graph
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    wall: WallId,
+    from: VertexId,
+    to: VertexId,
+}
+
+impl Edge {
+    fn other(&self, vertex: VertexId) -> VertexId {
+        if vertex == self.from {
+            self.to
+        } else {
+            self.from
+        }
+    }
+}
+
+/// Trace the closed loops formed by a set of edges, returns the loops and the unused edges
+fn trace_loops(map: &Map, edges: &[Edge]) -> (Vec<BoundaryLoop>, Vec<WallId>) {
+    let point = |v: VertexId| map.vertices[v.index()].xy();
+
+    let mut adjacent: BTreeMap<VertexId, Vec<usize>> = BTreeMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        adjacent.entry(edge.from).or_default().push(i);
+        adjacent.entry(edge.to).or_default().push(i);
+    }
+
+    // Dead ends can never be part of a loop, peel them off first
+    let mut removed = vec![false; edges.len()];
+    let mut stack: Vec<VertexId> = adjacent.keys().copied().collect();
+    while let Some(vertex) = stack.pop() {
+        let live: Vec<usize> = adjacent[&vertex]
+            .iter()
+            .copied()
+            .filter(|&e| !removed[e])
+            .collect();
+        if let [edge] = live[..] {
+            removed[edge] = true;
+            stack.push(edges[edge].other(vertex));
+        }
+    }
+
+    let mut used = removed.clone();
+    let mut loops = Vec::new();
+    let mut open = Vec::new();
+
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+
+        let first = edges[start].from;
+        let mut vertices = vec![first];
+        let mut walls = vec![edges[start].wall];
+        let mut chain = vec![start];
+        let mut current = edges[start].to;
+        let mut incoming = point(current) - point(first);
+
+        while current != first {
+            // Where loops touch, take the sharpest right turn so they stay separate
+            let next = adjacent[&current]
+                .iter()
+                .copied()
+                .filter(|&e| !used[e])
+                .min_by(|&a, &b| {
+                    let turn = |e: usize| {
+                        let out = point(edges[e].other(current)) - point(current);
+                        incoming.perp(&out).atan2(incoming.dot(&out))
+                    };
+                    turn(a).total_cmp(&turn(b))
+                });
+
+            let Some(next) = next else {
+                break;
+            };
+            used[next] = true;
+            chain.push(next);
+
+            vertices.push(current);
+            walls.push(edges[next].wall);
+            let to = edges[next].other(current);
+            incoming = point(to) - point(current);
+            current = to;
+        }
+
+        if current == first && vertices.len() >= 3 {
+            let points = vertices.iter().map(|&v| point(v)).collect();
+            loops.push(BoundaryLoop {
+                vertices,
+                walls,
+                points,
+            });
+        } else {
+            open.extend(chain.iter().map(|&e| edges[e].wall));
+        }
+    }
+
+    open.extend(
+        (0..edges.len())
+            .filter(|&e| removed[e])
+            .map(|e| edges[e].wall),
+    );
+
+    (loops, open)
+}
+
+/// Group loops into outlines with holes, nesting decides which loops are holes
+fn nest_loops(mut loops: Vec<BoundaryLoop>) -> Vec<Polygon> {
+    loops.sort_by(|a, b| b.signed_area().abs().total_cmp(&a.signed_area().abs()));
+
+    // Loops only contain smaller loops, so every container comes before its contents
+    let mut depth = vec![0; loops.len()];
+    let mut parent = vec![None; loops.len()];
+    for i in 0..loops.len() {
+        let sample = loops[i].sample_point();
+        for j in (0..i).rev() {
+            if loops[j].contains(&sample) {
+                depth[i] = depth[j] + 1;
+                parent[i] = Some(j);
+                break;
+            }
+        }
+    }
+
+    let mut polygons: BTreeMap<usize, Polygon> = BTreeMap::new();
+    let mut holes = Vec::new();
+    for (i, mut boundary) in loops.into_iter().enumerate() {
+        let is_hole = depth[i] % 2 == 1;
+        if boundary.is_clockwise() != is_hole {
+            boundary.reverse();
+        }
+
+        if is_hole {
+            holes.push((parent[i].expect("holes have a parent"), boundary));
+        } else {
+            polygons.insert(
+                i,
+                Polygon {
+                    outer: boundary,
+                    holes: Vec::new(),
+                },
+            );
+        }
+    }
+
+    for (outer, hole) in holes {
+        if let Some(polygon) = polygons.get_mut(&outer) {
+            polygon.holes.push(hole);
+        }
+    }
+
+    polygons.into_values().collect()
+}
+
+impl Map {
+    /// Rebuild the outline of every region from its walls, in region order
+    pub fn region_outlines(&self) -> Vec<RegionOutline> {
+        (0..self.regions.len())
+            .map(|i| self.region_outline(RegionId(i)))
+            .collect()
+    }
+
+    /// Rebuild the outline of a region from the walls which have it on either side.
+    ///
+    /// One-sided walls name the same region on both sides. Walls with missing vertices
+    /// can't be placed and are reported as open.
+    pub fn region_outline(&self, region: RegionId) -> RegionOutline {
+        let mut edges = Vec::new();
+        let mut open_walls = Vec::new();
+        let mut seen = BTreeSet::new();
+
+        for (id, wall) in self.walls_with_ids() {
+            let (r1, r2) = (wall.region1_index(), wall.region2_index());
+            if r1 != region && r2 != region {
+                continue;
+            }
+
+            let (from, to) = (wall.vertex1_index(), wall.vertex2_index());
+            if self.vertex(from).is_none() || self.vertex(to).is_none() {
+                open_walls.push(id);
+                continue;
+            }
+            // Zero length and repeated walls add nothing to the outline
+            if from == to || !seen.insert((from.min(to), from.max(to))) {
+                continue;
+            }
+
+            edges.push(Edge { wall: id, from, to });
+        }
+
+        let (loops, open) = trace_loops(self, &edges);
+        open_walls.extend(open);
+        open_walls.sort();
+
+        RegionOutline {
+            region,
+            polygons: nest_loops(loops),
+            open_walls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::add_loop;
+    use crate::map::{Region, Vertex, Wall};
+
+    fn test_map() -> Map {
+        let mut map = Map::new("test".to_string());
+        let hall = map.add_region(Region::new("hall".to_string(), 0.0, 128.0));
+        let pillar = map.add_region(Region::new("pillar".to_string(), 0.0, 0.0));
+
+        // Clockwise outer square with a counter-clockwise pillar in the middle
+        add_loop(
+            &mut map,
+            &[(0.0, 0.0), (0.0, 100.0), (100.0, 100.0), (100.0, 0.0)],
+            hall,
+            hall,
+        );
+        add_loop(
+            &mut map,
+            &[(40.0, 40.0), (60.0, 40.0), (60.0, 60.0), (40.0, 60.0)],
+            pillar,
+            hall,
+        );
+        map
+    }
+
+    #[test]
+    fn test_outline() {
+        let map = test_map();
+        let hall = map.region_outline(RegionId(0));
+
+        assert!(hall.is_closed());
+        assert_eq!(hall.polygons().len(), 1);
+
+        let polygon = &hall.polygons()[0];
+        assert!(!polygon.outer().is_clockwise());
+        assert_eq!(polygon.holes().len(), 1);
+        assert!(polygon.holes()[0].is_clockwise());
+        assert_eq!(hall.area(), 100.0 * 100.0 - 20.0 * 20.0);
+
+        assert!(hall.contains(&Point::new(10.0, 10.0)));
+        assert!(!hall.contains(&Point::new(50.0, 50.0)));
+        assert!(!hall.contains(&Point::new(150.0, 50.0)));
+
+        let pillar = map.region_outline(RegionId(1));
+        assert!(pillar.is_closed());
+        assert_eq!(pillar.area(), 20.0 * 20.0);
+        assert!(pillar.contains(&Point::new(50.0, 50.0)));
+
+        // Samples are off the outline for either winding, the hole is clockwise
+        for boundary in [polygon.outer(), &polygon.holes()[0]] {
+            let sample = boundary.sample_point();
+            let on_edge = boundary
+                .points()
+                .iter()
+                .any(|p| p.x == sample.x || p.y == sample.y);
+            assert!(boundary.contains(&sample) && !on_edge);
+        }
+    }
+
+    #[test]
+    fn test_loop_walls() {
+        let map = test_map();
+        let outline = map.region_outline(RegionId(0));
+        let outer = outline.polygons()[0].outer();
+
+        // Each wall joins the vertices on either side of it in the loop
+        for (i, wall) in outer.walls().iter().enumerate() {
+            let wall = map.wall(*wall).unwrap();
+            let ends = [wall.vertex1_index(), wall.vertex2_index()];
+            assert!(ends.contains(&outer.vertices()[i]));
+            assert!(ends.contains(&outer.vertices()[(i + 1) % outer.vertices().len()]));
+        }
+    }
+
+    #[test]
+    fn test_unclosed() {
+        let mut map = test_map();
+        let hall = RegionId(0);
+        let a = map.add_vertex(Vertex::new(10.0, 10.0, 0.0));
+        let b = map.add_vertex(Vertex::new(20.0, 10.0, 0.0));
+        let spur = map.add_wall(Wall::new("stone".to_string(), a, b, hall, hall));
+        let broken = map.add_wall(Wall::new("stone".to_string(), a, VertexId(99), hall, hall));
+
+        let outline = map.region_outline(hall);
+        assert!(!outline.is_closed());
+        assert_eq!(outline.open_walls(), &[spur, broken]);
+        assert_eq!(outline.polygons().len(), 1);
+
+        let empty = map.add_region(Region::new("empty".to_string(), 0.0, 0.0));
+        assert!(!map.region_outline(empty).is_closed());
+    }
+}
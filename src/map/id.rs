@@ -0,0 +1,45 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Define a typed index into one of the map's lists, WMP files refer to elements by position
+macro_rules! define_id {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+        pub struct $name(pub usize);
+
+        impl $name {
+            pub fn index(self) -> usize {
+                self.0
+            }
+        }
+
+        impl From<usize> for $name {
+            fn from(index: usize) -> Self {
+                Self(index)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+define_id!(
+    /// Index of a vertex in `Map::vertices`
+    VertexId
+);
+define_id!(
+    /// Index of a region in `Map::regions`
+    RegionId
+);
+define_id!(
+    /// Index of a wall in `Map::walls`
+    WallId
+);
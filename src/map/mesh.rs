@@ -0,0 +1,505 @@
+use nalgebra::{Vector2, Vector3};
+
+use super::geometry::{Point, RegionOutline};
+use super::triangulate::triangulate;
+use super::{Map, RegionId, Texture, WallId};
+
+/// An indexed triangle mesh in map space, X and Y on the plan and Z up
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mesh {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Vector2<f32>>,
+    triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn positions(&self) -> &[Vector3<f32>] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vector3<f32>] {
+        &self.normals
+    }
+
+    /// Texture coordinates, the origin is the top left of the texture
+    pub fn uvs(&self) -> &[Vector2<f32>] {
+        &self.uvs
+    }
+
+    /// Counter-clockwise triangles when seen from the side their normals face
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// Add a vertex, returning its index
+    pub fn push_vertex(
+        &mut self,
+        position: Vector3<f32>,
+        normal: Vector3<f32>,
+        uv: Vector2<f32>,
+    ) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        (self.positions.len() - 1) as u32
+    }
+
+    pub fn push_triangle(&mut self, triangle: [u32; 3]) {
+        self.triangles.push(triangle);
+    }
+
+    /// Add the vertices and triangles of another mesh
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend_from_slice(&other.positions);
+        self.normals.extend_from_slice(&other.normals);
+        self.uvs.extend_from_slice(&other.uvs);
+        self.triangles.extend(
+            other
+                .triangles
+                .iter()
+                .map(|t| t.map(|index| index + offset)),
+        );
+    }
+}
+
+/// Which part of a wall a piece of geometry covers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WallSection {
+    /// Full height wall on the edge of the level
+    Main,
+    /// Step between two ceilings
+    Upper,
+    /// Step between two floors
+    Lower,
+}
+
+/// What part of the level a surface belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SurfaceSource {
+    Floor(RegionId),
+    Ceiling(RegionId),
+    Wall { wall: WallId, section: WallSection },
+}
+
+/// A piece of level geometry along with where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Surface {
+    source: SurfaceSource,
+    region: RegionId,
+    mesh: Mesh,
+}
+
+impl Surface {
+    pub fn source(&self) -> SurfaceSource {
+        self.source
+    }
+
+    /// The region the surface is seen from
+    pub fn region(&self) -> RegionId {
+        self.region
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl Map {
+    /// Flat mesh filling a region's outline at the given height
+    fn region_cap(&self, region: RegionId, height: f32, facing_up: bool) -> Mesh {
+        let (normal, source) = if facing_up {
+            (Vector3::z(), SurfaceSource::Floor(region))
+        } else {
+            (-Vector3::z(), SurfaceSource::Ceiling(region))
+        };
+        let texture = self.material_texture(&self.source_material(source));
+
+        let mut mesh = Mesh::new();
+        for polygon in self.region_outline(region).polygons() {
+            let first = mesh.positions.len() as u32;
+            for point in polygon.points() {
+                // Floors and ceilings are mapped straight down onto the plan
+                let uv = texture.uv(Vector2::new(point.x, -point.y), Vector2::zeros());
+                mesh.push_vertex(Vector3::new(point.x, point.y, height), normal, uv);
+            }
+
+            for [a, b, c] in triangulate(polygon) {
+                let [a, b, c] = [a, b, c].map(|i| first + i as u32);
+                // Triangles come out counter-clockwise from above, flip them for ceilings
+                mesh.push_triangle(if facing_up { [a, b, c] } else { [a, c, b] });
+            }
+        }
+
+        mesh
+    }
+
+    /// Floor of a region at its floor height, facing up
+    pub fn floor_mesh(&self, region: RegionId) -> Mesh {
+        let height = self.region(region).map_or(0.0, |r| r.floor_height());
+        self.region_cap(region, height, true)
+    }
+
+    /// Ceiling of a region at its ceiling height, facing down into the room
+    pub fn ceiling_mesh(&self, region: RegionId) -> Mesh {
+        let height = self.region(region).map_or(0.0, |r| r.ceiling_height());
+        self.region_cap(region, height, false)
+    }
+
+    /// Floors and ceilings of every region, regions without a closed outline are left out
+    pub fn region_surfaces(&self) -> Vec<Surface> {
+        let mut surfaces = Vec::new();
+
+        for i in 0..self.regions.len() {
+            let region = RegionId(i);
+            let floor = self.floor_mesh(region);
+            let ceiling = self.ceiling_mesh(region);
+
+            if !floor.is_empty() {
+                surfaces.push(Surface {
+                    source: SurfaceSource::Floor(region),
+                    region,
+                    mesh: floor,
+                });
+            }
+            if !ceiling.is_empty() {
+                surfaces.push(Surface {
+                    source: SurfaceSource::Ceiling(region),
+                    region,
+                    mesh: ceiling,
+                });
+            }
+        }
+
+        surfaces
+    }
+
+    /// Whether a region lies to the left of the line from `a` to `b`
+    fn is_left_of(outline: &RegionOutline, a: Point, b: Point) -> bool {
+        let direction = b - a;
+        let left = Point::new(-direction.y, direction.x) * 1e-3;
+        outline.contains(&((a + b) / 2.0 + left))
+    }
+
+    /// Vertical quad along a wall between two heights, facing the given side.
+    ///
+    /// The texture is shifted by `offset` pixels.
+    fn wall_quad(
+        a: Point,
+        b: Point,
+        bottom: f32,
+        top: f32,
+        face_left: bool,
+        texture: &Texture,
+        offset: Vector2<f32>,
+    ) -> Mesh {
+        // The quad faces right of the line from a to b
+        let (a, b) = if face_left { (b, a) } else { (a, b) };
+        let direction = (b - a).normalize();
+        let normal = Vector3::new(direction.y, -direction.x, 0.0);
+
+        // Textures run along the wall and are anchored at height 0, so the steps on either
+        // side of a region line up
+        let length = (b - a).norm();
+        let mut mesh = Mesh::new();
+        let corners = [
+            (Vector3::new(a.x, a.y, bottom), 0.0),
+            (Vector3::new(b.x, b.y, bottom), length),
+            (Vector3::new(b.x, b.y, top), length),
+            (Vector3::new(a.x, a.y, top), 0.0),
+        ];
+        for (corner, u) in corners {
+            let uv = texture.uv(Vector2::new(u, -corner.z), offset);
+            mesh.push_vertex(corner, normal, uv);
+        }
+        mesh.push_triangle([0, 1, 2]);
+        mesh.push_triangle([0, 2, 3]);
+
+        mesh
+    }
+
+    /// The visible pieces of every wall.
+    ///
+    /// One-sided walls become a full height quad facing into their region. Walls between
+    /// two regions get a lower step facing the region with the lower floor and an upper
+    /// step facing the region with the higher ceiling, where those heights differ.
+    pub fn wall_surfaces(&self) -> Vec<Surface> {
+        let outlines = self.region_outlines();
+        let mut surfaces = Vec::new();
+
+        for (id, wall) in self.walls_with_ids() {
+            let (Some(v1), Some(v2)) = (
+                self.vertex(wall.vertex1_index()),
+                self.vertex(wall.vertex2_index()),
+            ) else {
+                continue;
+            };
+            let (a, b) = (v1.xy(), v2.xy());
+            if a == b {
+                continue;
+            }
+
+            // A missing region is treated like the other side of a one-sided wall
+            let (id1, id2) = match (
+                self.region(wall.region1_index()),
+                self.region(wall.region2_index()),
+            ) {
+                (Some(_), Some(_)) => (wall.region1_index(), wall.region2_index()),
+                (Some(_), None) => (wall.region1_index(), wall.region1_index()),
+                (None, Some(_)) => (wall.region2_index(), wall.region2_index()),
+                (None, None) => continue,
+            };
+            let (r1, r2) = (&self.regions[id1.index()], &self.regions[id2.index()]);
+            let left_of = |region: RegionId| {
+                outlines
+                    .get(region.index())
+                    .is_some_and(|outline| Self::is_left_of(outline, a, b))
+            };
+
+            let texture = self.material_texture(&self.source_material(SurfaceSource::Wall {
+                wall: id,
+                section: WallSection::Main,
+            }));
+            let offset = Vector2::new(wall.offset_x(), wall.offset_y());
+
+            let mut push = |section, region: RegionId, bottom: f32, top: f32, face_left| {
+                if top > bottom {
+                    surfaces.push(Surface {
+                        source: SurfaceSource::Wall { wall: id, section },
+                        region,
+                        mesh: Self::wall_quad(a, b, bottom, top, face_left, &texture, offset),
+                    });
+                }
+            };
+
+            if id1 == id2 {
+                push(
+                    WallSection::Main,
+                    id1,
+                    r1.floor_height(),
+                    r1.ceiling_height(),
+                    left_of(id1),
+                );
+                continue;
+            }
+
+            // Region 1 is assumed to be on the left when the outlines can't tell
+            let first_on_left = left_of(id1) || !left_of(id2);
+            let facing_left = |region: RegionId| (region == id1) == first_on_left;
+
+            let (low, high) = if r1.floor_height() <= r2.floor_height() {
+                (id1, id2)
+            } else {
+                (id2, id1)
+            };
+            let (low_region, high_region) =
+                (&self.regions[low.index()], &self.regions[high.index()]);
+            push(
+                WallSection::Lower,
+                low,
+                low_region.floor_height(),
+                high_region.floor_height().min(low_region.ceiling_height()),
+                facing_left(low),
+            );
+
+            let (high, low) = if r1.ceiling_height() >= r2.ceiling_height() {
+                (id1, id2)
+            } else {
+                (id2, id1)
+            };
+            let (low_region, high_region) =
+                (&self.regions[low.index()], &self.regions[high.index()]);
+            push(
+                WallSection::Upper,
+                high,
+                low_region.ceiling_height().max(high_region.floor_height()),
+                high_region.ceiling_height(),
+                facing_left(high),
+            );
+        }
+
+        surfaces
+    }
+
+    /// All level geometry, region floors and ceilings followed by the walls
+    pub fn surfaces(&self) -> Vec<Surface> {
+        let mut surfaces = self.region_surfaces();
+        surfaces.extend(self.wall_surfaces());
+        surfaces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::geometry::Point;
+    use crate::map::test_maps::{add_loop, square_room, two_rooms};
+    use crate::map::Region;
+
+    /// Total area of a mesh's triangles projected onto the plan, positive facing up
+    fn plan_area(mesh: &Mesh) -> f32 {
+        mesh.triangles()
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| mesh.positions()[i as usize].xy());
+                (b - a).perp(&(c - a)) / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_concave_with_holes() {
+        let mut map = Map::new("test".to_string());
+        let room = map.add_region(Region::new("room".to_string(), -8.0, 64.0));
+        let pillar = map.add_region(Region::new("pillar".to_string(), 0.0, 0.0));
+
+        // A U shaped room with a pillar in each arm
+        add_loop(
+            &mut map,
+            &[
+                (0.0, 0.0),
+                (90.0, 0.0),
+                (90.0, 90.0),
+                (60.0, 90.0),
+                (60.0, 30.0),
+                (30.0, 30.0),
+                (30.0, 90.0),
+                (0.0, 90.0),
+            ],
+            room,
+            room,
+        );
+        add_loop(
+            &mut map,
+            &[(10.0, 50.0), (20.0, 50.0), (20.0, 60.0), (10.0, 60.0)],
+            pillar,
+            room,
+        );
+        add_loop(
+            &mut map,
+            &[(70.0, 50.0), (80.0, 50.0), (80.0, 60.0), (70.0, 60.0)],
+            pillar,
+            room,
+        );
+
+        let area = 90.0 * 90.0 - 30.0 * 60.0 - 2.0 * 100.0;
+        let floor = map.floor_mesh(room);
+        assert_eq!(floor.positions().len(), 16);
+        assert!((plan_area(&floor) - area).abs() < 0.01);
+        assert!(floor.positions().iter().all(|p| p.z == -8.0));
+        assert!(floor.normals().iter().all(|n| *n == Vector3::z()));
+
+        // Every triangle is counter-clockwise from above and inside the room
+        let outline = map.region_outline(room);
+        for t in floor.triangles() {
+            let [a, b, c] = t.map(|i| floor.positions()[i as usize].xy());
+            assert!((b - a).perp(&(c - a)) > 0.0);
+            assert!(outline.contains(&Point::from((a + b + c) / 3.0)));
+        }
+
+        let ceiling = map.ceiling_mesh(room);
+        assert!((plan_area(&ceiling) + area).abs() < 0.01);
+        assert!(ceiling.normals().iter().all(|n| *n == -Vector3::z()));
+
+        let surfaces = map.region_surfaces();
+        assert_eq!(surfaces.len(), 4);
+        assert_eq!(surfaces[3].source(), SurfaceSource::Ceiling(pillar));
+    }
+
+    #[test]
+    fn test_walls() {
+        // The shared wall 1 runs up the middle
+        let map = two_rooms();
+
+        let surfaces = map.wall_surfaces();
+        let sections: Vec<(WallId, WallSection)> = surfaces
+            .iter()
+            .map(|s| match s.source() {
+                SurfaceSource::Wall { wall, section } => (wall, section),
+                other => panic!("Unexpected surface {:?}", other),
+            })
+            .collect();
+        assert_eq!(sections.len(), 6 + 2);
+        assert!(sections.contains(&(WallId(1), WallSection::Lower)));
+        assert!(sections.contains(&(WallId(1), WallSection::Upper)));
+        assert!(!sections.contains(&(WallId(1), WallSection::Main)));
+
+        for surface in &surfaces {
+            let mesh = surface.mesh();
+            let normal = mesh.normals()[0];
+            let (bottom, top) = (mesh.positions()[0].z, mesh.positions()[2].z);
+
+            match surface.source() {
+                SurfaceSource::Wall {
+                    wall: WallId(1),
+                    section,
+                } => {
+                    // Both steps face back into the room
+                    assert_eq!(normal, -Vector3::x());
+                    let expected = match section {
+                        WallSection::Lower => (0.0, 16.0),
+                        _ => (48.0, 64.0),
+                    };
+                    assert_eq!((bottom, top), expected);
+                }
+                _ => {
+                    // Main walls face into the middle of their region
+                    let [a, b, c] = mesh.triangles()[0].map(|i| mesh.positions()[i as usize]);
+                    assert!(((b - a).cross(&(c - a))).normalize().dot(&normal) > 0.99);
+                    let centre = if mesh.positions()[0].x > 64.0 || mesh.positions()[1].x > 64.0 {
+                        Vector3::new(96.0, 32.0, 0.0)
+                    } else {
+                        Vector3::new(32.0, 32.0, 0.0)
+                    };
+                    assert!((centre - mesh.positions()[0]).dot(&normal) > 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_texture_coordinates() {
+        let mut map = square_room();
+        let room = RegionId(0);
+        let wall = map.wall_mut(WallId(0)).unwrap();
+        wall.set_offset(8.0, 4.0);
+        wall.set_wall_texture("brick".to_string());
+        map.add_texture(
+            "brick".to_string(),
+            Texture::new("BRICK".to_string(), 32.0, 16.0).with_scale(2.0, 2.0),
+        );
+
+        // Untextured floors repeat every 64 units in world XY
+        let floor = map.floor_mesh(room);
+        let corner = floor
+            .positions()
+            .iter()
+            .position(|p| p.x == 64.0 && p.y == 64.0)
+            .unwrap();
+        assert_eq!(floor.uvs()[corner], Vector2::new(1.0, -1.0));
+
+        // A 32 x 16 pixel texture at scale 2 covers 64 x 32 units, shifted by the offset
+        let surfaces = map.wall_surfaces();
+        let wall = surfaces
+            .iter()
+            .find(|s| {
+                s.source()
+                    == SurfaceSource::Wall {
+                        wall: WallId(0),
+                        section: WallSection::Main,
+                    }
+            })
+            .unwrap();
+        assert_eq!(wall.mesh().uvs()[1], Vector2::new(1.25, 0.25));
+        assert_eq!(wall.mesh().uvs()[2], Vector2::new(1.25, -1.75));
+    }
+}
@@ -1,167 +1,216 @@
 #![allow(dead_code)]
+use crate::Result;
 use nalgebra::Vector3;
-use object::*;
-use region::Region;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Error, ErrorKind};
-use std::path::PathBuf;
-use wall::Wall;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
 
-type Vertex = Vector3<f32>;
+pub type Vertex = Vector3<f32>;
 
+pub mod export;
+pub mod geometry;
+pub mod id;
+pub mod mesh;
 pub mod object;
+pub mod parser;
+pub mod placement;
 pub mod region;
+pub mod svg;
+#[cfg(test)]
+mod test_maps;
+pub mod texture;
+pub mod triangulate;
+pub mod validate;
 pub mod wall;
 pub mod wdl;
+pub mod writer;
+pub use id::{RegionId, VertexId, WallId};
+pub use object::{Object, ObjectType};
+pub use parser::{Diagnostic, ParseMode, Severity};
+pub use region::Region;
+pub use svg::SvgOptions;
+pub use texture::Texture;
+pub use validate::{Element, Issue, IssueKind};
+pub use wall::Wall;
+pub use wdl::Wdl;
 
 // Note about indexing: the WMP files use direct array indexing when referring to vertices, regions, and walls.
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Map {
     name: String,
     vertices: Vec<Vertex>,
     regions: Vec<Region>,
     walls: Vec<Wall>,
     objects: Vec<Object>,
-}
-
-#[derive(Debug, Clone)]
-enum MapDataType {
-    Vertex,
-    Region,
-    Wall,
-    Comment,
-    Object,
+    textures: BTreeMap<String, Texture>,
 }
 
 impl Map {
+    /// An empty map
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
 
-    /// Load a map from a WMP file
-    pub fn parse_wmp(&mut self, filename: &PathBuf) -> Result<(), std::io::Error> {
-        self.name = filename
-            .file_stem()
-            .unwrap_or(OsStr::new("Unknown"))
-            .to_string_lossy()
-            .to_string();
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
 
-        //println!("Parsing WMP file: {:?}", self.name);
-
-        let file = match File::open(filename) {
-            Ok(file) => Ok(file),
-            Err(error) => match error.kind() {
-                ErrorKind::NotFound => Err(std::io::Error::new(
-                    ErrorKind::NotFound,
-                    format!("File not found: {:?}", filename.to_str()),
-                )),
-                other_error => Err(Error::new(other_error, "Failed to open file")),
-            },
-        };
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
 
-        // The file should be safe to unwrap as errors are handled above
-        let reader = BufReader::new(file.unwrap());
+    pub fn vertex(&self, id: VertexId) -> Option<&Vertex> {
+        self.vertices.get(id.index())
+    }
 
-        self.vertices = Vec::new();
-        self.regions = Vec::new();
-        self.walls = Vec::new();
+    pub fn vertex_mut(&mut self, id: VertexId) -> Option<&mut Vertex> {
+        self.vertices.get_mut(id.index())
+    }
 
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.trim();
-            let line = line.split_once(';').map_or(line, |(before, _)| before); // Trim everything after ";"
-            let line = line.trim_end();
+    /// Append a vertex, walls refer to it by the returned id
+    pub fn add_vertex(&mut self, vertex: Vertex) -> VertexId {
+        self.vertices.push(vertex);
+        VertexId(self.vertices.len() - 1)
+    }
 
-            if line.is_empty() || line.starts_with('#') {
-                // Skip empty lines and comments
-                continue;
-            }
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
 
-            let parts: Vec<&str> = line.split_whitespace().collect();
-
-            let line_type = match parts[0] {
-                "VERTEX" => MapDataType::Vertex,
-                "REGION" => MapDataType::Region,
-                "WALL" => MapDataType::Wall,
-                "PLAYER_START" | "THING" | "ACTOR" => MapDataType::Object,
-                _ => MapDataType::Comment,
-            };
-
-            match line_type {
-                MapDataType::Vertex => {
-                    // Parse vertex data
-                    let x: f32 = parts[1].parse().unwrap_or_default();
-                    let y: f32 = parts[2].parse().unwrap_or_default();
-                    let z: f32 = parts[3].parse().unwrap_or_default();
-                    self.vertices.push(Vector3::new(x, y, z));
-                }
-                MapDataType::Region => {
-                    // Parse region data
-                    let name = parts[1].to_string();
-                    let floor_hgt: f32 = parts[2].parse().unwrap_or_default();
-                    let ceil_hgt: f32 = parts[3].parse().unwrap_or_default();
-                    self.regions.push(Region::new(name, floor_hgt, ceil_hgt));
-                }
-                MapDataType::Wall => {
-                    // Parse wall data
-                    let name = parts[1].to_string();
-                    let vertex1_index: usize = parts[2].parse().unwrap_or_default();
-                    let vertex2_index: usize = parts[3].parse().unwrap_or_default();
-                    let region1_index: usize = parts[4].parse().unwrap_or_default();
-                    let region2_index: usize = parts[5].parse().unwrap_or_default();
-                    let offset_x: f32 = parts[6].parse().unwrap_or_default();
-                    let offset_y: f32 = parts[7].parse().unwrap_or_default();
-                    self.walls.push(Wall::new(
-                        name,
-                        vertex1_index,
-                        vertex2_index,
-                        region1_index,
-                        region2_index,
-                        offset_x,
-                        offset_y,
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                    ));
-                }
-                MapDataType::Object => {
-                    let offset: usize = match parts[0] {
-                        "PLAYER_START" => 1,
-                        _ => 0,
-                    };
-                    self.objects.push(Object::new(
-                        ObjectType::from(parts[0]),
-                        parts[1 - offset].to_string(),
-                        Vector3::new(
-                            parts[2 - offset].parse().unwrap_or_default(),
-                            parts[3 - offset].parse().unwrap_or_default(),
-                            0.0,
-                        ),
-                        parts[4 - offset].parse().unwrap_or_default(),
-                        parts[5 - offset].parse().unwrap_or_default(),
-                    ));
-                }
-                _ => {}
-            }
-        }
+    pub fn region(&self, id: RegionId) -> Option<&Region> {
+        self.regions.get(id.index())
+    }
+
+    pub fn region_mut(&mut self, id: RegionId) -> Option<&mut Region> {
+        self.regions.get_mut(id.index())
+    }
 
+    /// Append a region, walls and objects refer to it by the returned id
+    pub fn add_region(&mut self, region: Region) -> RegionId {
+        self.regions.push(region);
+        RegionId(self.regions.len() - 1)
+    }
+
+    pub fn walls(&self) -> &[Wall] {
+        &self.walls
+    }
+
+    pub fn wall(&self, id: WallId) -> Option<&Wall> {
+        self.walls.get(id.index())
+    }
+
+    pub fn wall_mut(&mut self, id: WallId) -> Option<&mut Wall> {
+        self.walls.get_mut(id.index())
+    }
+
+    pub fn add_wall(&mut self, wall: Wall) -> WallId {
+        self.walls.push(wall);
+        WallId(self.walls.len() - 1)
+    }
+
+    /// Iterate over the walls along with their ids
+    pub fn walls_with_ids(&self) -> impl Iterator<Item = (WallId, &Wall)> {
+        self.walls
+            .iter()
+            .enumerate()
+            .map(|(i, wall)| (WallId(i), wall))
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn objects_mut(&mut self) -> &mut [Object] {
+        &mut self.objects
+    }
+
+    pub fn add_object(&mut self, object: Object) {
+        self.objects.push(object);
+    }
+
+    /// Remove an object, nothing refers to objects so the others are simply shifted down
+    pub fn remove_object(&mut self, index: usize) -> Option<Object> {
+        (index < self.objects.len()).then(|| self.objects.remove(index))
+    }
+
+    /// Textures by name, as defined in the WDL scripts
+    pub fn textures(&self) -> &BTreeMap<String, Texture> {
+        &self.textures
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&Texture> {
+        self.textures.get(name)
+    }
+
+    pub fn add_texture(&mut self, name: String, texture: Texture) {
+        self.textures.insert(name, texture);
+    }
+
+    /// The texture a material is drawn with, a default one named after the material when no
+    /// texture has been defined
+    pub fn material_texture(&self, material: &str) -> Texture {
+        self.texture(material).cloned().unwrap_or_else(|| {
+            Texture::new(
+                material.to_string(),
+                texture::DEFAULT_TEXTURE_SIZE,
+                texture::DEFAULT_TEXTURE_SIZE,
+            )
+        })
+    }
+
+    /// Load a map from a WMP file, malformed values are read as 0 like the engine does
+    pub fn parse_wmp(&mut self, filename: &Path) -> Result<()> {
+        self.parse_wmp_with(filename, ParseMode::Lenient)?;
         Ok(())
     }
 
-    /// Create a list of vertices from the map data
-    pub fn create_vertex_list(&self) -> Vec<String> {
-        let mut output_data: Vec<String> = Vec::new();
-        for (vertex, region) in self.vertices.iter().zip(self.regions.iter()) {
-            let vert1 = format!("{},{},{}", vertex.x, vertex.y, region.floor_height());
-            let vert2 = format!("{},{},{}", vertex.x, vertex.y, region.ceiling_height());
+    /// Load a map from a WMP file, returning the warnings found
+    pub fn parse_wmp_with(&mut self, filename: &Path, mode: ParseMode) -> Result<Vec<Diagnostic>> {
+        let text = match fs::read(filename) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(error) => {
+                return Err(match error.kind() {
+                    ErrorKind::NotFound => io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("File not found: {:?}", filename.to_str()),
+                    ),
+                    other_error => io::Error::new(other_error, "Failed to open file"),
+                }
+                .into())
+            }
+        };
 
-            output_data.push(vert1);
-            output_data.push(vert2);
-        }
+        let diagnostics = self.parse_wmp_str(filename, &text, mode)?;
 
-        output_data
+        // Named only once loaded, a map that fails to parse is left as it was
+        self.name = filename
+            .file_stem()
+            .unwrap_or(OsStr::new("Unknown"))
+            .to_string_lossy()
+            .to_string();
+        Ok(diagnostics)
+    }
+
+    /// Create a list of the floor and ceiling vertices of every region
+    pub fn create_vertex_list(&self) -> Vec<String> {
+        self.region_surfaces()
+            .iter()
+            .flat_map(|surface| surface.mesh().positions())
+            .map(|p| format!("{},{},{}", p.x, p.y, p.z))
+            .collect()
     }
 
     /// Creates a CSV string from the vertex data
@@ -174,3 +223,67 @@ impl Map {
         output.join("\n")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn test_map() -> Map {
+        let mut map = Map::new("test".to_string());
+        let a = map.add_vertex(Vertex::new(0.0, 0.0, 0.0));
+        let b = map.add_vertex(Vertex::new(64.0, 0.0, 0.0));
+        let hall = map.add_region(Region::new("hall".to_string(), 0.0, 128.0));
+        map.add_wall(Wall::new("stone".to_string(), a, b, hall, hall).with_offset(4.0, 0.0));
+        map.add_object(Object::new(
+            ObjectType::Thing,
+            "lamp".to_string(),
+            Vertex::new(8.0, 8.0, 0.0),
+            0.0,
+            hall,
+        ));
+        map
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut map = test_map();
+
+        map.region_mut(RegionId(0)).unwrap().set_floor_height(-16.0);
+        map.objects_mut()[0].set_position(Vertex::new(32.0, 16.0, 0.0));
+        map.wall_mut(WallId(0))
+            .unwrap()
+            .set_vertices(VertexId(1), VertexId(0));
+
+        assert_eq!(map.region(RegionId(0)).unwrap().floor_height(), -16.0);
+        assert_eq!(map.objects()[0].position().x, 32.0);
+        assert_eq!(map.wall(WallId(0)).unwrap().vertex1_index(), VertexId(1));
+        assert!(map.vertex(VertexId(2)).is_none());
+        assert!(map.remove_object(0).is_some());
+        assert!(map.objects().is_empty());
+    }
+
+    #[test]
+    fn test_parse_wmp_failure() {
+        let mut map = test_map();
+        let file = env::temp_dir().join(format!("a3conv_bad_{}.wmp", std::process::id()));
+        fs::write(&file, "VERTEX 0 zero 0;\n").unwrap();
+
+        assert!(map.parse_wmp_with(&file, ParseMode::Strict).is_err());
+        assert!(map.parse_wmp(Path::new("missing.wmp")).is_err());
+        assert_eq!(map.name(), "test");
+        assert_eq!(map.walls().len(), 1);
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let map = test_map();
+        let json = serde_json::to_string(&map).unwrap();
+
+        assert!(json.contains("\"vertex1_index\":0"));
+        assert_eq!(serde_json::from_str::<Map>(&json).unwrap(), map);
+    }
+}
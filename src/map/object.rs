@@ -1,6 +1,10 @@
-use super::Vertex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone)]
+use super::{RegionId, Vertex};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjectType {
     #[default]
     Actor,
@@ -18,13 +22,25 @@ impl From<&str> for ObjectType {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+impl ObjectType {
+    /// Keyword starting the object's line in a WMP file
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ObjectType::Actor => "ACTOR",
+            ObjectType::PlayerStart => "PLAYER_START",
+            ObjectType::Thing => "THING",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Object {
     object_type: ObjectType,
     name: String,
     position: Vertex,
     angle: f32,
-    region: usize,
+    region: RegionId,
 }
 
 impl Object {
@@ -33,7 +49,7 @@ impl Object {
         name: String,
         position: Vertex,
         angle: f32,
-        region: usize,
+        region: RegionId,
     ) -> Self {
         Object {
             object_type,
@@ -43,4 +59,41 @@ impl Object {
             region,
         }
     }
+
+    pub fn object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn position(&self) -> &Vertex {
+        &self.position
+    }
+
+    pub fn set_position(&mut self, position: Vertex) {
+        self.position = position;
+    }
+
+    /// Facing angle in radians
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    pub fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    pub fn region(&self) -> RegionId {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: RegionId) {
+        self.region = region;
+    }
 }
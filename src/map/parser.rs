@@ -0,0 +1,411 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+
+use super::{Map, Object, ObjectType, Region, RegionId, VertexId, Wall};
+use crate::{Error, Result};
+
+/// How the parser treats malformed lines
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Any malformed line is an error
+    Strict,
+    /// Malformed values become 0 and are reported as warnings, like the engine does
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Not a problem in itself, like a check that couldn't be done
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while parsing, lines and columns start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    severity: Severity,
+    file: PathBuf,
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn into_error(self) -> Error {
+        Error::MapParse {
+            file: self.file,
+            line: self.line,
+            column: self.column,
+            message: self.message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// A word of a WMP line along with the column it starts at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+/// Split a line into tokens, everything after `;` or a leading `#` is a comment
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    if line.trim_start().starts_with('#') {
+        return Vec::new();
+    }
+    let line = line.split_once(';').map_or(line, |(before, _)| before);
+
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (index, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(index),
+            (true, Some(s)) => {
+                tokens.push(Token {
+                    text: &line[s..index],
+                    column: s + 1,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    file: &'a Path,
+    mode: ParseMode,
+    line: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Parser<'_> {
+    fn diagnostic(&self, severity: Severity, column: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            file: self.file.to_path_buf(),
+            line: self.line,
+            column,
+            message,
+        }
+    }
+
+    fn warn(&mut self, column: usize, message: String) {
+        let warning = self.diagnostic(Severity::Warning, column, message);
+        self.diagnostics.push(warning);
+    }
+
+    /// Report a malformed value, an error in strict mode and a warning otherwise
+    fn malformed(&mut self, column: usize, message: String) -> Result<()> {
+        match self.mode {
+            ParseMode::Strict => Err(self
+                .diagnostic(Severity::Error, column, message)
+                .into_error()),
+            ParseMode::Lenient => {
+                self.warn(column, message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Get a field of the line, missing fields fall back to an empty string when lenient
+    fn field<'t>(&mut self, tokens: &[Token<'t>], index: usize, what: &str) -> Result<Token<'t>> {
+        match tokens.get(index) {
+            Some(token) => Ok(*token),
+            None => {
+                let column = tokens.last().map_or(1, |t| t.column + t.text.len());
+                self.malformed(
+                    column,
+                    format!(
+                        "{} is missing its {} (field {})",
+                        tokens[0].text, what, index
+                    ),
+                )?;
+                Ok(Token { text: "", column })
+            }
+        }
+    }
+
+    fn name<'t>(&mut self, tokens: &[Token<'t>], index: usize) -> Result<String> {
+        Ok(self.field(tokens, index, "name")?.text.to_string())
+    }
+
+    fn number<'t, T: FromStr + Default>(
+        &mut self,
+        tokens: &[Token<'t>],
+        index: usize,
+        what: &str,
+    ) -> Result<T> {
+        let token = self.field(tokens, index, what)?;
+        if token.text.is_empty() {
+            // Already reported as missing
+            return Ok(T::default());
+        }
+
+        match token.text.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => {
+                self.malformed(token.column, format!("Invalid {} {:?}", what, token.text))?;
+                Ok(T::default())
+            }
+        }
+    }
+
+    /// Warn about fields after the ones the keyword uses
+    fn check_extra<'t>(&mut self, tokens: &[Token<'t>], count: usize) {
+        if let Some(extra) = tokens.get(count) {
+            self.warn(
+                extra.column,
+                format!("Ignoring extra fields after {}", tokens[0].text),
+            );
+        }
+    }
+
+    fn parse(mut self, map: &mut Map, text: &str) -> Result<Vec<Diagnostic>> {
+        // DOS editors end files with an EOF character
+        let text = text.trim_end_matches('\x1a');
+        for (index, line) in text.lines().enumerate() {
+            self.line = index + 1;
+
+            let tokens = tokenize(line);
+            let Some(keyword) = tokens.first() else {
+                // Skip empty lines and comments
+                continue;
+            };
+
+            match keyword.text {
+                "VERTEX" => {
+                    let x: f32 = self.number(&tokens, 1, "x coordinate")?;
+                    let y: f32 = self.number(&tokens, 2, "y coordinate")?;
+                    let z: f32 = self.number(&tokens, 3, "z coordinate")?;
+                    self.check_extra(&tokens, 4);
+                    map.vertices.push(Vector3::new(x, y, z));
+                }
+                "REGION" => {
+                    let name = self.name(&tokens, 1)?;
+                    let floor_hgt: f32 = self.number(&tokens, 2, "floor height")?;
+                    let ceil_hgt: f32 = self.number(&tokens, 3, "ceiling height")?;
+                    self.check_extra(&tokens, 4);
+                    map.regions.push(Region::new(name, floor_hgt, ceil_hgt));
+                }
+                "WALL" => {
+                    let name = self.name(&tokens, 1)?;
+                    let vertex1_index: usize = self.number(&tokens, 2, "first vertex index")?;
+                    let vertex2_index: usize = self.number(&tokens, 3, "second vertex index")?;
+                    let region1_index: usize = self.number(&tokens, 4, "first region index")?;
+                    let region2_index: usize = self.number(&tokens, 5, "second region index")?;
+                    let offset_x: f32 = self.number(&tokens, 6, "x offset")?;
+                    let offset_y: f32 = self.number(&tokens, 7, "y offset")?;
+                    self.check_extra(&tokens, 8);
+                    map.walls.push(
+                        Wall::new(
+                            name,
+                            VertexId(vertex1_index),
+                            VertexId(vertex2_index),
+                            RegionId(region1_index),
+                            RegionId(region2_index),
+                        )
+                        .with_offset(offset_x, offset_y),
+                    );
+                }
+                "PLAYER_START" | "THING" | "ACTOR" => {
+                    // PLAYER_START has no name, the keyword doubles as one
+                    let offset: usize = match keyword.text {
+                        "PLAYER_START" => 1,
+                        _ => 0,
+                    };
+                    let name = self.name(&tokens, 1 - offset)?;
+                    let x: f32 = self.number(&tokens, 2 - offset, "x coordinate")?;
+                    let y: f32 = self.number(&tokens, 3 - offset, "y coordinate")?;
+                    let angle: f32 = self.number(&tokens, 4 - offset, "angle")?;
+                    let region: usize = self.number(&tokens, 5 - offset, "region index")?;
+                    self.check_extra(&tokens, 6 - offset);
+                    map.objects.push(Object::new(
+                        ObjectType::from(keyword.text),
+                        name,
+                        Vector3::new(x, y, 0.0),
+                        angle,
+                        RegionId(region),
+                    ));
+                }
+                other => {
+                    let message = format!("Unknown keyword {:?}", other);
+                    self.malformed(keyword.column, message)?;
+                }
+            }
+        }
+
+        Ok(self.diagnostics)
+    }
+}
+
+impl Map {
+    /// Load a map from WMP source text, `file` is only used for diagnostics.
+    ///
+    /// Returns the warnings found. In strict mode the first problem is returned as an error.
+    pub fn parse_wmp_str(
+        &mut self,
+        file: &Path,
+        text: &str,
+        mode: ParseMode,
+    ) -> Result<Vec<Diagnostic>> {
+        let parser = Parser {
+            file,
+            mode,
+            line: 0,
+            diagnostics: Vec::new(),
+        };
+
+        // Parse into a fresh map so an error leaves this one as it was
+        let mut parsed = Map::default();
+        let diagnostics = parser.parse(&mut parsed, text)?;
+        self.vertices = parsed.vertices;
+        self.regions = parsed.regions;
+        self.walls = parsed.walls;
+        self.objects = parsed.objects;
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MAP: &str = "# Test map
+VERTEX 0 0 0;
+VERTEX 64.5 0 0 ; trailing comment
+VERTEX 64 64 0;
+REGION floor 0 32;
+WALL wall1 0 1 0 0 0 0;
+THING lamp 10 10 1.5 0;
+PLAYER_START 5 5 0 0;
+";
+
+    fn parse(text: &str, mode: ParseMode) -> (Map, Result<Vec<Diagnostic>>) {
+        let mut map = Map::default();
+        let result = map.parse_wmp_str(Path::new("test.wmp"), text, mode);
+        (map, result)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("  WALL  a 1;2 3");
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    text: "WALL",
+                    column: 3
+                },
+                Token {
+                    text: "a",
+                    column: 9
+                },
+                Token {
+                    text: "1",
+                    column: 11
+                },
+            ]
+        );
+        assert!(tokenize("# VERTEX 1 2 3").is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        let (map, result) = parse(TEST_MAP, ParseMode::Strict);
+
+        assert!(result.unwrap().is_empty());
+        assert_eq!(map.vertices.len(), 3);
+        assert_eq!(map.vertices[1].x, 64.5);
+        assert_eq!(map.regions.len(), 1);
+        assert_eq!(map.walls.len(), 1);
+        assert_eq!(map.objects.len(), 2);
+
+        // A trailing DOS end of file character is ignored
+        let (map, result) = parse(&format!("{}\x1a", TEST_MAP), ParseMode::Strict);
+        assert!(result.unwrap().is_empty());
+        assert_eq!(map.vertices.len(), 3);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let text = "VERTEX 0 0 0;\nWALL w 0 x 0 0 0 0;\nVERTEX 1 2;\nFOO 1;\n";
+
+        let (mut map, result) = parse(TEST_MAP, ParseMode::Strict);
+        result.unwrap();
+        let result = map.parse_wmp_str(Path::new("test.wmp"), text, ParseMode::Strict);
+        match result {
+            Err(Error::MapParse { line, column, .. }) => assert_eq!((line, column), (2, 10)),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        // A map that fails to parse leaves the loaded one as it was
+        assert_eq!(map.vertices.len(), 3);
+        assert_eq!(map.objects.len(), 2);
+
+        let (map, result) = parse(text, ParseMode::Lenient);
+        let warnings = result.unwrap();
+        let positions: Vec<(usize, usize)> =
+            warnings.iter().map(|w| (w.line(), w.column())).collect();
+
+        assert_eq!(positions, vec![(2, 10), (3, 11), (4, 1)]);
+        assert!(warnings.iter().all(|w| w.severity() == Severity::Warning));
+        assert_eq!(map.vertices.len(), 2);
+        assert_eq!(map.walls.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "test.wmp:2:10: warning: Invalid second vertex index \"x\""
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use super::geometry::{Point, RegionOutline};
+use super::validate::{Element, Issue, IssueKind};
+use super::{Map, RegionId, Severity, Wdl};
+
+/// The region containing a point, preferring `hint` and then the smallest region when
+/// outlines overlap
+fn containing_region(
+    outlines: &[RegionOutline],
+    point: &Point,
+    hint: Option<RegionId>,
+) -> Option<RegionId> {
+    let candidates: Vec<&RegionOutline> = outlines
+        .iter()
+        .filter(|outline| outline.contains(point))
+        .collect();
+
+    if candidates
+        .iter()
+        .any(|outline| Some(outline.region()) == hint)
+    {
+        return hint;
+    }
+    candidates
+        .iter()
+        .min_by(|a, b| a.area().total_cmp(&b.area()))
+        .map(|outline| outline.region())
+}
+
+impl Map {
+    /// The region whose outline contains a point on the plan
+    pub fn region_at(&self, point: &Point) -> Option<RegionId> {
+        containing_region(&self.region_outlines(), point, None)
+    }
+
+    /// Put every object into the region it stands in and onto that region's floor.
+    ///
+    /// Objects whose WDL definition is `CEIL_ATTACHED` hang from the ceiling instead. An
+    /// object found in a different region than the one it names is moved to the region it's
+    /// in, one outside every region keeps its region. Both are reported.
+    #[must_use]
+    pub fn place_objects(&mut self, wdl: &Wdl) -> Vec<Issue> {
+        let outlines = self.region_outlines();
+        let mut issues = Vec::new();
+
+        for (i, object) in self.objects.iter_mut().enumerate() {
+            let declared = object.region();
+            let region = match containing_region(&outlines, &object.position().xy(), Some(declared))
+            {
+                Some(region) => region,
+                None => {
+                    issues.push(Issue::new(
+                        Severity::Warning,
+                        IssueKind::ObjectOutsideRegion,
+                        Element::Object(i),
+                        format!(
+                            "object is outside every region, keeping region {}",
+                            declared
+                        ),
+                    ));
+                    declared
+                }
+            };
+
+            if region != declared {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    IssueKind::WrongObjectRegion,
+                    Element::Object(i),
+                    format!(
+                        "object is in region {} rather than region {}",
+                        region, declared
+                    ),
+                ));
+                object.set_region(region);
+            }
+
+            let Some(region) = self.regions.get(region.index()) else {
+                continue;
+            };
+            let mut position = *object.position();
+            position.z = match wdl.is_ceiling_attached(object.name()) {
+                true => region.ceiling_height(),
+                false => region.floor_height(),
+            };
+            object.set_position(position);
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::two_rooms;
+    use crate::map::{Object, ObjectType, Vertex};
+
+    #[test]
+    fn test_place_objects() {
+        // The step is on the right
+        let mut map = two_rooms();
+        let (room, step) = (RegionId(0), RegionId(1));
+
+        for (name, x, region) in [
+            ("guard", 32.0, room),
+            ("crate", 96.0, room),
+            ("lamp", 96.0, step),
+            ("bird", 200.0, step),
+        ] {
+            map.add_object(Object::new(
+                ObjectType::Thing,
+                name.to_string(),
+                Vertex::new(x, 32.0, 0.0),
+                0.0,
+                region,
+            ));
+        }
+
+        let mut wdl = Wdl::new();
+        wdl.parse_wdl_str("THING lamp { FLAGS CEIL_ATTACHED; }");
+        assert_eq!(map.region_at(&Point::new(96.0, 32.0)), Some(step));
+
+        let issues: Vec<(IssueKind, Element)> = map
+            .place_objects(&wdl)
+            .iter()
+            .map(|issue| (issue.kind(), issue.element()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (IssueKind::WrongObjectRegion, Element::Object(1)),
+                (IssueKind::ObjectOutsideRegion, Element::Object(3)),
+            ]
+        );
+
+        let placed: Vec<(RegionId, f32)> = map
+            .objects()
+            .iter()
+            .map(|o| (o.region(), o.position().z))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![(room, 0.0), (step, 16.0), (step, 48.0), (step, 16.0)]
+        );
+    }
+}
@@ -1,8 +1,16 @@
-#[derive(Debug, Default, Clone)]
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Region {
     name: String,
     floor_height: f32,
     ceiling_height: f32,
+
+    // Textures are read from the *.wdl file
+    floor_texture: String,
+    ceiling_texture: String,
 }
 
 impl Region {
@@ -11,14 +19,50 @@ impl Region {
             name,
             floor_height,
             ceiling_height,
+            ..Default::default()
         }
     }
 
+    pub fn with_textures(mut self, floor_texture: String, ceiling_texture: String) -> Self {
+        self.floor_texture = floor_texture;
+        self.ceiling_texture = ceiling_texture;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn floor_height(&self) -> f32 {
         self.floor_height
     }
 
+    pub fn set_floor_height(&mut self, floor_height: f32) {
+        self.floor_height = floor_height;
+    }
+
     pub fn ceiling_height(&self) -> f32 {
         self.ceiling_height
     }
+
+    pub fn set_ceiling_height(&mut self, ceiling_height: f32) {
+        self.ceiling_height = ceiling_height;
+    }
+
+    pub fn set_textures(&mut self, floor_texture: String, ceiling_texture: String) {
+        self.floor_texture = floor_texture;
+        self.ceiling_texture = ceiling_texture;
+    }
+
+    pub fn floor_texture(&self) -> &str {
+        &self.floor_texture
+    }
+
+    pub fn ceiling_texture(&self) -> &str {
+        &self.ceiling_texture
+    }
 }
@@ -0,0 +1,326 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use super::geometry::Point;
+use super::{Map, ObjectType};
+use crate::Result;
+
+// Colour of the lowest and highest floors
+const LOW_COLOUR: [f32; 3] = [59.0, 76.0, 192.0];
+const HIGH_COLOUR: [f32; 3] = [180.0, 4.0, 38.0];
+
+// Length of the arrow showing which way an object faces, in pixels
+const ARROW_LENGTH: f32 = 12.0;
+
+const STYLE: &str = "
+    .region { fill-opacity: 0.5; stroke: none; fill-rule: evenodd }
+    .wall { stroke: black; stroke-width: 2 }
+    .two-sided { stroke: gray; stroke-width: 1; stroke-dasharray: 4 2 }
+    .vertex { fill: black }
+    .object { stroke-width: 1.5 }
+    .player-start { fill: limegreen; stroke: limegreen }
+    .thing { fill: royalblue; stroke: royalblue }
+    .actor { fill: orangered; stroke: orangered }
+    .region-label { font: 10px sans-serif; text-anchor: middle }
+    .wall-label { font: 8px sans-serif; text-anchor: middle; fill: dimgray }
+";
+
+/// Settings for `Map::render_svg`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SvgOptions {
+    scale: f32,
+    margin: f32,
+    region_labels: bool,
+    wall_labels: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            margin: 16.0,
+            region_labels: false,
+            wall_labels: false,
+        }
+    }
+}
+
+impl SvgOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pixels per map unit
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Space around the level in pixels
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Write the name of each region inside it
+    pub fn with_region_labels(mut self, region_labels: bool) -> Self {
+        self.region_labels = region_labels;
+        self
+    }
+
+    /// Write the index of each wall next to it
+    pub fn with_wall_labels(mut self, wall_labels: bool) -> Self {
+        self.wall_labels = wall_labels;
+        self
+    }
+}
+
+/// Round to hundredths so the output stays short and stable
+fn num(value: f32) -> String {
+    ((value * 100.0).round() / 100.0 + 0.0).to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Fill colour of a floor, `t` running from 0 for the lowest to 1 for the highest
+fn ramp(t: f32) -> String {
+    let [r, g, b] = [0, 1, 2].map(|i| LOW_COLOUR[i] + (HIGH_COLOUR[i] - LOW_COLOUR[i]) * t);
+    format!("#{:02x}{:02x}{:02x}", r as u8, g as u8, b as u8)
+}
+
+impl Map {
+    /// Draw a top-down plan of the level.
+    ///
+    /// Regions are filled by floor height from blue for the lowest to red for the highest.
+    /// One-sided walls are solid and two-sided walls dashed. Player starts, things and actors
+    /// are drawn as dots with an arrow for their angle, each with its name as a tooltip.
+    pub fn render_svg(&self, options: &SvgOptions) -> String {
+        // The plan's Y axis points up while SVG's points down
+        let points = self
+            .vertices()
+            .iter()
+            .chain(self.objects().iter().map(|o| o.position()));
+        let (mut min, mut max) = (Point::repeat(f32::MAX), Point::repeat(f32::MIN));
+        for point in points {
+            min = min.inf(&point.xy());
+            max = max.sup(&point.xy());
+        }
+        if min.x > max.x {
+            (min, max) = (Point::zeros(), Point::zeros());
+        }
+        let to_svg = |p: &Point| {
+            let x = (p.x - min.x) * options.scale + options.margin;
+            let y = (max.y - p.y) * options.scale + options.margin;
+            Point::new(x, y)
+        };
+        let size = (max - min) * options.scale + Point::repeat(options.margin * 2.0);
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+            num(size.x),
+            num(size.y)
+        )
+        .unwrap();
+        writeln!(svg, "<title>{}</title>", escape(self.name())).unwrap();
+        writeln!(svg, "<style>{}</style>", STYLE).unwrap();
+
+        let floors = self.regions().iter().map(|r| r.floor_height());
+        let lowest = floors.clone().fold(f32::MAX, f32::min);
+        let highest = floors.fold(f32::MIN, f32::max);
+
+        let outlines = self.region_outlines();
+        for (outline, region) in outlines.iter().zip(self.regions()) {
+            let mut path = String::new();
+            for polygon in outline.polygons() {
+                for boundary in std::iter::once(polygon.outer()).chain(polygon.holes()) {
+                    for (i, point) in boundary.points().iter().enumerate() {
+                        let point = to_svg(point);
+                        let command = if i == 0 { "M" } else { "L" };
+                        write!(path, "{}{} {} ", command, num(point.x), num(point.y)).unwrap();
+                    }
+                    path.push('Z');
+                }
+            }
+            if path.is_empty() {
+                continue;
+            }
+
+            let t = match highest > lowest {
+                true => (region.floor_height() - lowest) / (highest - lowest),
+                false => 0.0,
+            };
+            writeln!(
+                svg,
+                "<path class=\"region\" fill=\"{}\" d=\"{}\"><title>{}</title></path>",
+                ramp(t),
+                path,
+                escape(region.name())
+            )
+            .unwrap();
+        }
+
+        for (id, wall) in self.walls_with_ids() {
+            let (Some(v1), Some(v2)) = (
+                self.vertex(wall.vertex1_index()),
+                self.vertex(wall.vertex2_index()),
+            ) else {
+                continue;
+            };
+            let (a, b) = (to_svg(&v1.xy()), to_svg(&v2.xy()));
+
+            let (r1, r2) = (wall.region1_index(), wall.region2_index());
+            let two_sided = r1 != r2 && self.region(r1).is_some() && self.region(r2).is_some();
+            writeln!(
+                svg,
+                "<line class=\"{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+                if two_sided { "wall two-sided" } else { "wall" },
+                num(a.x),
+                num(a.y),
+                num(b.x),
+                num(b.y)
+            )
+            .unwrap();
+
+            if options.wall_labels {
+                let middle = (a + b) / 2.0;
+                writeln!(
+                    svg,
+                    "<text class=\"wall-label\" x=\"{}\" y=\"{}\">{}</text>",
+                    num(middle.x),
+                    num(middle.y),
+                    id
+                )
+                .unwrap();
+            }
+        }
+
+        for vertex in self.vertices() {
+            let point = to_svg(&vertex.xy());
+            writeln!(
+                svg,
+                "<circle class=\"vertex\" cx=\"{}\" cy=\"{}\" r=\"2\"/>",
+                num(point.x),
+                num(point.y)
+            )
+            .unwrap();
+        }
+
+        for object in self.objects() {
+            let class = match object.object_type() {
+                ObjectType::PlayerStart => "player-start",
+                ObjectType::Thing => "thing",
+                ObjectType::Actor => "actor",
+            };
+            let centre = to_svg(&object.position().xy());
+            let (sin, cos) = object.angle().sin_cos();
+            let tip = centre + Point::new(cos, -sin) * ARROW_LENGTH;
+            let name = match object.name() {
+                "" => object.object_type().keyword(),
+                name => name,
+            };
+
+            writeln!(
+                svg,
+                "<g class=\"object {}\"><title>{}</title><circle cx=\"{}\" cy=\"{}\" r=\"4\"/><line x1=\"{2}\" y1=\"{3}\" x2=\"{}\" y2=\"{}\"/></g>",
+                class,
+                escape(name),
+                num(centre.x),
+                num(centre.y),
+                num(tip.x),
+                num(tip.y)
+            )
+            .unwrap();
+        }
+
+        if options.region_labels {
+            for (outline, region) in outlines.iter().zip(self.regions()) {
+                // Label the largest piece of the region at the middle of its outer boundary
+                let Some(polygon) = outline
+                    .polygons()
+                    .iter()
+                    .max_by(|a, b| a.area().total_cmp(&b.area()))
+                else {
+                    continue;
+                };
+                let points = polygon.outer().points();
+                let centre = points.iter().sum::<Point>() / points.len() as f32;
+                let centre = to_svg(&centre);
+                writeln!(
+                    svg,
+                    "<text class=\"region-label\" x=\"{}\" y=\"{}\">{}</text>",
+                    num(centre.x),
+                    num(centre.y),
+                    escape(region.name())
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(svg, "</svg>").unwrap();
+        svg
+    }
+
+    /// Write the plan drawn by `render_svg` to a file
+    pub fn write_svg(&self, filename: &Path, options: &SvgOptions) -> Result<()> {
+        fs::write(filename, self.render_svg(options))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::two_rooms;
+    use crate::map::{Object, RegionId, Vertex};
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_render_svg() {
+        let mut map = two_rooms();
+        map.add_object(Object::new(
+            ObjectType::PlayerStart,
+            String::new(),
+            Vertex::new(32.0, 32.0, 0.0),
+            FRAC_PI_2,
+            RegionId(0),
+        ));
+
+        let options = SvgOptions::new()
+            .with_region_labels(true)
+            .with_wall_labels(true);
+        let svg = map.render_svg(&options);
+        let lines: Vec<&str> = svg.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"160\" height=\"96\" viewBox=\"0 0 160 96\">"
+        );
+        assert_eq!(lines.last(), Some(&"</svg>"));
+
+        // The lowest floor is blue and the highest red
+        assert!(svg.contains("fill=\"#3b4cc0\""));
+        assert!(svg.contains("fill=\"#b40426\""));
+
+        // Map Y is flipped so the origin is at the bottom left
+        assert!(lines.contains(&"<line class=\"wall\" x1=\"16\" y1=\"80\" x2=\"80\" y2=\"80\"/>"));
+        assert!(lines
+            .contains(&"<line class=\"wall two-sided\" x1=\"80\" y1=\"80\" x2=\"80\" y2=\"16\"/>"));
+        assert_eq!(svg.matches("<circle class=\"vertex\"").count(), 6);
+
+        // Facing along Y points up the image
+        assert!(svg.contains("<title>PLAYER_START</title><circle cx=\"48\" cy=\"48\" r=\"4\"/><line x1=\"48\" y1=\"48\" x2=\"48\" y2=\"36\"/>"));
+        assert!(lines.contains(&"<text class=\"region-label\" x=\"48\" y=\"48\">room</text>"));
+        assert!(lines.contains(&"<text class=\"wall-label\" x=\"80\" y=\"48\">1</text>"));
+
+        let plain = map.render_svg(&SvgOptions::new());
+        assert!(!plain.contains("<text"));
+    }
+}
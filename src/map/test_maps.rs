@@ -0,0 +1,66 @@
+//! Small maps shared by the tests of the map modules
+
+use super::{Map, Region, RegionId, Vertex, VertexId, Wall};
+
+/// Add a closed loop of `stone` walls between two regions, wall i runs from point i to i + 1
+pub fn add_loop(map: &mut Map, points: &[(f32, f32)], inside: RegionId, outside: RegionId) {
+    let first = map.vertices().len();
+    for &(x, y) in points {
+        map.add_vertex(Vertex::new(x, y, 0.0));
+    }
+    for i in 0..points.len() {
+        let a = VertexId(first + i);
+        let b = VertexId(first + (i + 1) % points.len());
+        map.add_wall(Wall::new("stone".to_string(), a, b, inside, outside));
+    }
+}
+
+/// A 64 x 64 `room` from 0 to 64 high, walled counter-clockwise starting at the origin
+pub fn square_room() -> Map {
+    let mut map = Map::new("test".to_string());
+    let room = map.add_region(Region::new("room".to_string(), 0.0, 64.0));
+    add_loop(
+        &mut map,
+        &[(0.0, 0.0), (64.0, 0.0), (64.0, 64.0), (0.0, 64.0)],
+        room,
+        room,
+    );
+    map
+}
+
+/// Two 64 x 64 squares side by side, the `room` from 0 to 64 high and the `step` on its right
+/// from 16 to 48. Walls 0 to 3 go round the room, wall 1 is the one they share.
+pub fn two_rooms() -> Map {
+    let mut map = Map::new("test".to_string());
+    let room = map.add_region(Region::new("room".to_string(), 0.0, 64.0));
+    let step = map.add_region(Region::new("step".to_string(), 16.0, 48.0));
+
+    for (x, y) in [
+        (0.0, 0.0),
+        (64.0, 0.0),
+        (128.0, 0.0),
+        (128.0, 64.0),
+        (64.0, 64.0),
+        (0.0, 64.0),
+    ] {
+        map.add_vertex(Vertex::new(x, y, 0.0));
+    }
+    for (a, b, r1, r2) in [
+        (0, 1, room, room),
+        (1, 4, room, step),
+        (4, 5, room, room),
+        (5, 0, room, room),
+        (1, 2, step, step),
+        (2, 3, step, step),
+        (3, 4, step, step),
+    ] {
+        map.add_wall(Wall::new(
+            "stone".to_string(),
+            VertexId(a),
+            VertexId(b),
+            r1,
+            r2,
+        ));
+    }
+    map
+}
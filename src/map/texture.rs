@@ -0,0 +1,80 @@
+use nalgebra::Vector2;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Size of a texture in map units when nothing else is known
+pub const DEFAULT_TEXTURE_SIZE: f32 = 64.0;
+
+/// How a texture is laid onto the level, built from the WDL definitions
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Texture {
+    // Name of the converted image, the stem of the bitmap file
+    image: String,
+    // Bitmap file and the x, y, dx, dy a cut out texture takes from it
+    cut: Option<(String, [u32; 4])>,
+    // Size of the bitmap in pixels
+    size: Vector2<f32>,
+    // Map units covered by one pixel
+    scale: Vector2<f32>,
+}
+
+impl Default for Texture {
+    fn default() -> Self {
+        Self {
+            image: String::new(),
+            cut: None,
+            size: Vector2::repeat(DEFAULT_TEXTURE_SIZE),
+            scale: Vector2::repeat(1.0),
+        }
+    }
+}
+
+impl Texture {
+    pub fn new(image: String, width: f32, height: f32) -> Self {
+        Self {
+            image,
+            size: Vector2::new(width, height),
+            ..Default::default()
+        }
+    }
+
+    /// A texture cut out of a larger bitmap file, converted on its own into `image`
+    pub fn with_cut(mut self, file: String, rect: [u32; 4]) -> Self {
+        self.cut = Some((file, rect));
+        self
+    }
+
+    /// Set the `SCALE_XY` of the texture, the map units covered by one pixel
+    pub fn with_scale(mut self, scale_x: f32, scale_y: f32) -> Self {
+        self.scale = Vector2::new(scale_x, scale_y);
+        self
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// The bitmap file and rectangle of a cut out texture
+    pub fn cut(&self) -> Option<(&str, [u32; 4])> {
+        self.cut.as_ref().map(|(file, rect)| (file.as_str(), *rect))
+    }
+
+    pub fn size(&self) -> Vector2<f32> {
+        self.size
+    }
+
+    pub fn scale(&self) -> Vector2<f32> {
+        self.scale
+    }
+
+    /// Size of one repeat of the texture in map units
+    pub fn world_size(&self) -> Vector2<f32> {
+        self.size.component_mul(&self.scale)
+    }
+
+    /// Texture coordinates of a point given in map units, shifted by an offset in pixels
+    pub fn uv(&self, point: Vector2<f32>, offset: Vector2<f32>) -> Vector2<f32> {
+        (point.component_div(&self.scale) + offset).component_div(&self.size)
+    }
+}
@@ -0,0 +1,221 @@
+use super::geometry::{point_in_polygon, Point, Polygon};
+
+/// Twice the signed area of a triangle, positive when counter-clockwise
+fn orient(a: Point, b: Point, c: Point) -> f32 {
+    (b - a).perp(&(c - b))
+}
+
+/// Whether a turn is too shallow to form a triangle
+fn is_degenerate(a: Point, b: Point, c: Point) -> bool {
+    orient(a, b, c).abs() <= f32::EPSILON * (b - a).norm() * (c - b).norm()
+}
+
+/// Inclusive point in triangle test, the triangle is counter-clockwise
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    orient(a, b, p) >= 0.0 && orient(b, c, p) >= 0.0 && orient(c, a, p) >= 0.0
+}
+
+/// Join a clockwise hole into a counter-clockwise outline with a pair of bridge edges.
+///
+/// The bridge runs from the hole's rightmost vertex to a visible outline vertex, found by
+/// casting a ray along +x as described in Eberly's "Triangulation by Ear Clipping".
+fn bridge_hole(outline: &mut Vec<usize>, hole: &[usize], points: &[Point]) {
+    let (start, m) = hole
+        .iter()
+        .enumerate()
+        .max_by(|(_, &a), (_, &b)| points[a].x.total_cmp(&points[b].x))
+        .map(|(i, &v)| (i, points[v]))
+        .expect("holes aren't empty");
+
+    let n = outline.len();
+    let at = |i: usize| points[outline[i % n]];
+
+    // Nearest outline edge hit by the ray
+    let mut hit: Option<(f32, usize)> = None;
+    for i in 0..n {
+        let (a, b) = (at(i), at(i + 1));
+        if (a.y > m.y) == (b.y > m.y) || a.y == b.y {
+            continue;
+        }
+        let x = a.x + (m.y - a.y) / (b.y - a.y) * (b.x - a.x);
+        if x >= m.x && !hit.is_some_and(|(best, _)| best <= x) {
+            hit = Some((x, i));
+        }
+    }
+
+    let visible = match hit {
+        Some((x, i)) => {
+            let hit_point = Point::new(x, m.y);
+            let mut visible = if at(i).x > at(i + 1).x {
+                i
+            } else {
+                (i + 1) % n
+            };
+            let p = at(visible);
+
+            // Reflex vertices inside the search triangle can block the view of p, the one
+            // closest in angle to the ray is always visible
+            let (a, b, c) = if orient(m, hit_point, p) >= 0.0 {
+                (m, hit_point, p)
+            } else {
+                (m, p, hit_point)
+            };
+            let mut best = None;
+            for j in 0..n {
+                let v = at(j);
+                let reflex = orient(at(j + n - 1), v, at(j + 1)) < 0.0;
+                if j == visible || v == p || !reflex || !in_triangle(v, a, b, c) {
+                    continue;
+                }
+                let d = v - m;
+                let key = (d.y.atan2(d.x).abs(), d.norm());
+                if !best.is_some_and(|(best_key, _)| best_key <= key) {
+                    best = Some((key, j));
+                }
+            }
+            if let Some((_, j)) = best {
+                visible = j;
+            }
+            visible
+        }
+        // The hole isn't inside the outline, join it to the nearest vertex anyway
+        None => (0..n)
+            .min_by(|&a, &b| (at(a) - m).norm().total_cmp(&(at(b) - m).norm()))
+            .expect("outlines aren't empty"),
+    };
+
+    let mut spliced = Vec::with_capacity(n + hole.len() + 2);
+    spliced.extend_from_slice(&outline[..=visible]);
+    spliced.extend((0..=hole.len()).map(|k| hole[(start + k) % hole.len()]));
+    spliced.extend_from_slice(&outline[visible..]);
+    *outline = spliced;
+}
+
+/// Two outline corners joined by a segment inside the outline that crosses no edge
+fn find_diagonal(outline: &[usize], points: &[Point]) -> Option<(usize, usize)> {
+    let corners: Vec<Point> = outline.iter().map(|&v| points[v]).collect();
+    let n = corners.len();
+    let crosses = |a: Point, b: Point, c: Point, d: Point| {
+        orient(a, b, c) * orient(a, b, d) < 0.0 && orient(c, d, a) * orient(c, d, b) < 0.0
+    };
+
+    // Corners next to each other, including the first and last, are joined by an edge
+    (0..n)
+        .flat_map(|i| (i + 2..n).map(move |j| (i, j)))
+        .filter(|&(i, j)| i != 0 || j != n - 1)
+        .find(|&(i, j)| {
+            let (a, b) = (corners[i], corners[j]);
+            a != b
+                && point_in_polygon(&((a + b) / 2.0), &corners)
+                && (0..n).all(|k| !crosses(a, b, corners[k], corners[(k + 1) % n]))
+        })
+}
+
+/// Clip ears off a counter-clockwise outline until only one triangle is left.
+///
+/// Parts of a tangled outline that no diagonal can split off are left out.
+fn clip_ears(mut outline: Vec<usize>, points: &[Point]) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::new();
+
+    while outline.len() >= 3 {
+        let n = outline.len();
+        let corner = |i: usize| (outline[(i + n - 1) % n], outline[i], outline[(i + 1) % n]);
+
+        let is_ear = |i: usize| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            orient(pa, pb, pc) > 0.0
+                && outline.iter().all(|&v| {
+                    let p = points[v];
+                    p == pa || p == pb || p == pc || !in_triangle(p, pa, pb, pc)
+                })
+        };
+
+        let degenerate = (0..n).find(|&i| {
+            let (a, b, c) = corner(i);
+            is_degenerate(points[a], points[b], points[c])
+        });
+        if let Some(i) = degenerate {
+            // Collinear points and spikes add no area
+            outline.remove(i);
+            continue;
+        }
+
+        // Self-touching outlines can leave no clean ear, settle for any convex corner
+        let ear = (0..n).find(|&i| is_ear(i)).or_else(|| {
+            (0..n).find(|&i| {
+                let (a, b, c) = corner(i);
+                orient(points[a], points[b], points[c]) > 0.0
+            })
+        });
+        let Some(ear) = ear else {
+            // Without a convex corner the outline is tangled, split it along a diagonal that
+            // stays inside it or give up on what's left
+            if let Some((i, j)) = find_diagonal(&outline, points) {
+                let first = outline[i..=j].to_vec();
+                let second = [&outline[j..], &outline[..=i]].concat();
+                triangles.extend(clip_ears(first, points));
+                triangles.extend(clip_ears(second, points));
+            }
+            break;
+        };
+
+        let (a, b, c) = corner(ear);
+        triangles.push([a, b, c]);
+        outline.remove(ear);
+    }
+
+    triangles
+}
+
+/// Split a polygon into counter-clockwise triangles, concave outlines and holes included.
+///
+/// Indices refer to `Polygon::points`, the outline's points followed by each hole's.
+pub fn triangulate(polygon: &Polygon) -> Vec<[usize; 3]> {
+    let points = polygon.points();
+
+    let mut outline: Vec<usize> = (0..polygon.outer().points().len()).collect();
+    let mut holes = Vec::new();
+    let mut offset = outline.len();
+    for hole in polygon.holes() {
+        holes.push((offset..offset + hole.points().len()).collect::<Vec<usize>>());
+        offset += hole.points().len();
+    }
+
+    // Bridge the rightmost holes first so later rays can't cross earlier bridges
+    holes.sort_by(|a, b| {
+        let max_x = |h: &Vec<usize>| h.iter().map(|&v| points[v].x).fold(f32::MIN, f32::max);
+        max_x(b).total_cmp(&max_x(a))
+    });
+    for hole in &holes {
+        bridge_hole(&mut outline, hole, &points);
+    }
+
+    clip_ears(outline, &points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_ears() {
+        let points: Vec<Point> = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 1.0), (0.0, 4.0)]
+            .iter()
+            .map(|&(x, y)| Point::new(x, y))
+            .collect();
+
+        // A concave outline gives one triangle less than it has corners, all of them clean
+        let triangles = clip_ears((0..5).collect(), &points);
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles
+            .iter()
+            .all(|&[a, b, c]| orient(points[a], points[b], points[c]) > 0.0));
+
+        // A clockwise outline has no convex corners, no inside out triangles are forced onto it
+        let triangles = clip_ears(vec![4, 2, 1, 0], &points);
+        assert!(triangles
+            .iter()
+            .all(|&[a, b, c]| orient(points[a], points[b], points[c]) > 0.0));
+    }
+}
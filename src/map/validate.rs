@@ -0,0 +1,438 @@
+use std::fmt;
+
+use super::geometry::Point;
+use super::{Map, RegionId, Severity, VertexId, WallId};
+
+/// Vertices closer than this are treated as the same point
+pub const VERTEX_TOLERANCE: f32 = 0.01;
+
+/// The part of a map an issue was found in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Element {
+    Vertex(VertexId),
+    Region(RegionId),
+    Wall(WallId),
+    /// An object, by its index in the map's object list
+    Object(usize),
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Vertex(id) => write!(f, "vertex {}", id),
+            Element::Region(id) => write!(f, "region {}", id),
+            Element::Wall(id) => write!(f, "wall {}", id),
+            Element::Object(index) => write!(f, "object {}", index),
+        }
+    }
+}
+
+/// What is wrong with a map element
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// A wall refers to a vertex that doesn't exist
+    MissingVertex,
+    /// A wall refers to a region that doesn't exist
+    MissingRegion,
+    /// A wall starts and ends at the same point
+    ZeroLengthWall,
+    /// A vertex is on top of an earlier one
+    DuplicateVertex,
+    /// A wall crosses, overlaps or ends on another one
+    CrossingWalls,
+    /// No walls border the region
+    EmptyRegion,
+    /// The region's ceiling is below its floor
+    CeilingBelowFloor,
+    /// An object is in a region that doesn't exist
+    MissingObjectRegion,
+    /// An object is outside the outline of its region
+    ObjectOutsideRegion,
+    /// An object's position couldn't be checked, its region isn't closed
+    UncheckedObject,
+    /// An object is inside a different region than the one it names
+    WrongObjectRegion,
+}
+
+/// A problem found by `Map::validate`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    severity: Severity,
+    kind: IssueKind,
+    element: Element,
+    message: String,
+}
+
+impl Issue {
+    pub(crate) fn new(
+        severity: Severity,
+        kind: IssueKind,
+        element: Element,
+        message: String,
+    ) -> Self {
+        Self {
+            severity,
+            kind,
+            element,
+            message,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn kind(&self) -> IssueKind {
+        self.kind
+    }
+
+    pub fn element(&self) -> Element {
+        self.element
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.element, self.severity, self.message)
+    }
+}
+
+/// Whether a point lies on a segment away from its ends
+fn touches_inside(p: Point, a: Point, b: Point) -> bool {
+    let along = b - a;
+    let length = along.norm();
+    let distance = along.perp(&(p - a)).abs() / length;
+    let t = along.dot(&(p - a)) / length;
+    distance < VERTEX_TOLERANCE && t > VERTEX_TOLERANCE && t < length - VERTEX_TOLERANCE
+}
+
+/// Whether two segments cross, overlap or one ends on the other, sharing an end doesn't count
+fn segments_cross(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+    let side = |p: Point, q: Point, r: Point| (q - p).perp(&(r - p));
+    let (d1, d2) = (side(b1, b2, a1), side(b1, b2, a2));
+    let (d3, d4) = (side(a1, a2, b1), side(a1, a2, b2));
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+
+    // T-junctions and collinear overlaps put an end of one on the inside of the other, unless
+    // both walls join the same two points
+    let same = |p: Point, q: Point| (p - q).norm() < VERTEX_TOLERANCE;
+    touches_inside(b1, a1, a2)
+        || touches_inside(b2, a1, a2)
+        || touches_inside(a1, b1, b2)
+        || touches_inside(a2, b1, b2)
+        || (same(a1, b1) && same(a2, b2))
+        || (same(a1, b2) && same(a2, b1))
+}
+
+impl Map {
+    /// Check the map for problems that trip up the geometry and exporters.
+    ///
+    /// Errors are broken references and impossible values, warnings are things the engine
+    /// copes with but which are probably mistakes. Issues come out grouped by check.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        // Walls pointing at missing vertices or regions
+        let mut segments = Vec::new();
+        for (id, wall) in self.walls_with_ids() {
+            for vertex in [wall.vertex1_index(), wall.vertex2_index()] {
+                if self.vertex(vertex).is_none() {
+                    issues.push(Issue::new(
+                        Severity::Error,
+                        IssueKind::MissingVertex,
+                        Element::Wall(id),
+                        format!("vertex {} doesn't exist", vertex),
+                    ));
+                }
+            }
+            for region in [wall.region1_index(), wall.region2_index()] {
+                if self.region(region).is_none() {
+                    issues.push(Issue::new(
+                        Severity::Error,
+                        IssueKind::MissingRegion,
+                        Element::Wall(id),
+                        format!("region {} doesn't exist", region),
+                    ));
+                }
+            }
+
+            let (Some(v1), Some(v2)) = (
+                self.vertex(wall.vertex1_index()),
+                self.vertex(wall.vertex2_index()),
+            ) else {
+                continue;
+            };
+            let (a, b) = (v1.xy(), v2.xy());
+            if (b - a).norm() < VERTEX_TOLERANCE {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    IssueKind::ZeroLengthWall,
+                    Element::Wall(id),
+                    "wall has no length".to_string(),
+                ));
+            } else {
+                segments.push((id, a, b));
+            }
+        }
+
+        for (i, vertex) in self.vertices().iter().enumerate() {
+            let earlier = self.vertices()[..i]
+                .iter()
+                .position(|other| (other.xy() - vertex.xy()).norm() < VERTEX_TOLERANCE);
+            if let Some(earlier) = earlier {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    IssueKind::DuplicateVertex,
+                    Element::Vertex(VertexId(i)),
+                    format!("vertex is on top of vertex {}", earlier),
+                ));
+            }
+        }
+
+        for (i, &(id, a1, a2)) in segments.iter().enumerate() {
+            for &(other, b1, b2) in &segments[i + 1..] {
+                if segments_cross(a1, a2, b1, b2) {
+                    issues.push(Issue::new(
+                        Severity::Error,
+                        IssueKind::CrossingWalls,
+                        Element::Wall(id),
+                        format!("wall crosses or touches wall {}", other),
+                    ));
+                }
+            }
+        }
+
+        let outlines = self.region_outlines();
+        for (i, region) in self.regions().iter().enumerate() {
+            let id = RegionId(i);
+            let bordered = self
+                .walls()
+                .iter()
+                .any(|w| w.region1_index() == id || w.region2_index() == id);
+            if !bordered {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    IssueKind::EmptyRegion,
+                    Element::Region(id),
+                    "no walls border the region".to_string(),
+                ));
+            }
+            if region.ceiling_height() < region.floor_height() {
+                issues.push(Issue::new(
+                    Severity::Error,
+                    IssueKind::CeilingBelowFloor,
+                    Element::Region(id),
+                    format!(
+                        "ceiling at {} is below the floor at {}",
+                        region.ceiling_height(),
+                        region.floor_height()
+                    ),
+                ));
+            }
+        }
+
+        for (i, object) in self.objects().iter().enumerate() {
+            let Some(outline) = outlines.get(object.region().index()) else {
+                issues.push(Issue::new(
+                    Severity::Error,
+                    IssueKind::MissingObjectRegion,
+                    Element::Object(i),
+                    format!("region {} doesn't exist", object.region()),
+                ));
+                continue;
+            };
+
+            // Open outlines can't tell inside from outside
+            if !outline.is_closed() || outline.polygons().is_empty() {
+                issues.push(Issue::new(
+                    Severity::Info,
+                    IssueKind::UncheckedObject,
+                    Element::Object(i),
+                    format!(
+                        "region {} isn't closed, the object's position wasn't checked",
+                        object.region()
+                    ),
+                ));
+                continue;
+            }
+            if !outline.contains(&object.position().xy()) {
+                issues.push(Issue::new(
+                    Severity::Warning,
+                    IssueKind::ObjectOutsideRegion,
+                    Element::Object(i),
+                    format!("object is outside region {}", object.region()),
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_maps::square_room;
+    use crate::map::{Object, ObjectType, Region, Vertex, Wall};
+
+    #[test]
+    fn test_validate() {
+        let mut map = square_room();
+        let room = RegionId(0);
+
+        // A clean room has no issues
+        map.add_object(Object::new(
+            ObjectType::Thing,
+            "lamp".to_string(),
+            Vertex::new(32.0, 32.0, 0.0),
+            0.0,
+            room,
+        ));
+        assert_eq!(map.validate(), vec![]);
+
+        map.add_object(Object::new(
+            ObjectType::Actor,
+            "ghost".to_string(),
+            Vertex::new(100.0, 32.0, 0.0),
+            0.0,
+            room,
+        ));
+        let issues = map.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind(), IssueKind::ObjectOutsideRegion);
+        assert_eq!(issues[0].element(), Element::Object(1));
+
+        let broken = map.add_region(Region::new("broken".to_string(), 32.0, 16.0));
+        let duplicate = map.add_vertex(Vertex::new(64.0, 0.0, 0.0));
+        map.add_wall(Wall::new(
+            "diagonal".to_string(),
+            VertexId(0),
+            VertexId(2),
+            room,
+            room,
+        ));
+        map.add_wall(Wall::new(
+            "cross".to_string(),
+            VertexId(1),
+            VertexId(3),
+            room,
+            RegionId(7),
+        ));
+        map.add_wall(Wall::new(
+            "dot".to_string(),
+            VertexId(1),
+            duplicate,
+            room,
+            room,
+        ));
+        map.add_wall(Wall::new(
+            "dangling".to_string(),
+            VertexId(0),
+            VertexId(9),
+            room,
+            room,
+        ));
+        // A wall ending in the middle of another and one running along it
+        let tee = map.add_vertex(Vertex::new(32.0, 0.0, 0.0));
+        let below = map.add_vertex(Vertex::new(32.0, -16.0, 0.0));
+        map.add_wall(Wall::new("tee".to_string(), tee, below, room, room));
+        let left = map.add_vertex(Vertex::new(16.0, 0.0, 0.0));
+        let right = map.add_vertex(Vertex::new(48.0, 0.0, 0.0));
+        map.add_wall(Wall::new("overlap".to_string(), left, right, room, room));
+        map.add_object(Object::new(
+            ObjectType::Actor,
+            "lost".to_string(),
+            Vertex::new(32.0, 32.0, 0.0),
+            0.0,
+            RegionId(5),
+        ));
+
+        let issues: Vec<(Severity, IssueKind, Element)> = map
+            .validate()
+            .iter()
+            .map(|issue| (issue.severity(), issue.kind(), issue.element()))
+            .collect();
+        // The room is no longer closed, so the lamp and ghost can't be checked
+        assert_eq!(
+            issues,
+            vec![
+                (
+                    Severity::Error,
+                    IssueKind::MissingRegion,
+                    Element::Wall(WallId(5))
+                ),
+                (
+                    Severity::Warning,
+                    IssueKind::ZeroLengthWall,
+                    Element::Wall(WallId(6))
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::MissingVertex,
+                    Element::Wall(WallId(7))
+                ),
+                (
+                    Severity::Warning,
+                    IssueKind::DuplicateVertex,
+                    Element::Vertex(duplicate)
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::CrossingWalls,
+                    Element::Wall(WallId(0))
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::CrossingWalls,
+                    Element::Wall(WallId(0))
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::CrossingWalls,
+                    Element::Wall(WallId(4))
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::CrossingWalls,
+                    Element::Wall(WallId(8))
+                ),
+                (
+                    Severity::Warning,
+                    IssueKind::EmptyRegion,
+                    Element::Region(broken)
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::CeilingBelowFloor,
+                    Element::Region(broken)
+                ),
+                (
+                    Severity::Info,
+                    IssueKind::UncheckedObject,
+                    Element::Object(0)
+                ),
+                (
+                    Severity::Info,
+                    IssueKind::UncheckedObject,
+                    Element::Object(1)
+                ),
+                (
+                    Severity::Error,
+                    IssueKind::MissingObjectRegion,
+                    Element::Object(2)
+                ),
+            ]
+        );
+
+        assert_eq!(
+            map.validate()[0].to_string(),
+            "wall 5: error: region 7 doesn't exist"
+        );
+    }
+}
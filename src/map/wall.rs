@@ -1,10 +1,16 @@
-#[derive(Debug, Default, Clone)]
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{RegionId, VertexId};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Wall {
     name: String,
-    vertex1_index: usize,
-    vertex2_index: usize,
-    region1_index: usize,
-    region2_index: usize,
+    vertex1_index: VertexId,
+    vertex2_index: VertexId,
+    region1_index: RegionId,
+    region2_index: RegionId,
 
     // Offsets are used for texture alignment
     offset_x: f32,
@@ -17,17 +23,13 @@ pub struct Wall {
 }
 
 impl Wall {
+    /// A wall from the first vertex to the second, between two regions
     pub fn new(
         name: String,
-        vertex1_index: usize,
-        vertex2_index: usize,
-        region1_index: usize,
-        region2_index: usize,
-        offset_x: f32,
-        offset_y: f32,
-        wall_texture: String,
-        floor_texture: String,
-        ceiling_texture: String,
+        vertex1_index: VertexId,
+        vertex2_index: VertexId,
+        region1_index: RegionId,
+        region2_index: RegionId,
     ) -> Self {
         Self {
             name,
@@ -35,11 +37,87 @@ impl Wall {
             vertex2_index,
             region1_index,
             region2_index,
-            offset_x,
-            offset_y,
-            wall_texture,
-            floor_texture,
-            ceiling_texture,
+            ..Default::default()
         }
     }
+
+    pub fn with_offset(mut self, offset_x: f32, offset_y: f32) -> Self {
+        self.set_offset(offset_x, offset_y);
+        self
+    }
+
+    pub fn with_textures(
+        mut self,
+        wall_texture: String,
+        floor_texture: String,
+        ceiling_texture: String,
+    ) -> Self {
+        self.wall_texture = wall_texture;
+        self.floor_texture = floor_texture;
+        self.ceiling_texture = ceiling_texture;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn vertex1_index(&self) -> VertexId {
+        self.vertex1_index
+    }
+
+    pub fn vertex2_index(&self) -> VertexId {
+        self.vertex2_index
+    }
+
+    pub fn set_vertices(&mut self, vertex1_index: VertexId, vertex2_index: VertexId) {
+        self.vertex1_index = vertex1_index;
+        self.vertex2_index = vertex2_index;
+    }
+
+    pub fn region1_index(&self) -> RegionId {
+        self.region1_index
+    }
+
+    pub fn region2_index(&self) -> RegionId {
+        self.region2_index
+    }
+
+    pub fn set_regions(&mut self, region1_index: RegionId, region2_index: RegionId) {
+        self.region1_index = region1_index;
+        self.region2_index = region2_index;
+    }
+
+    pub fn offset_x(&self) -> f32 {
+        self.offset_x
+    }
+
+    pub fn offset_y(&self) -> f32 {
+        self.offset_y
+    }
+
+    pub fn set_offset(&mut self, offset_x: f32, offset_y: f32) {
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+    }
+
+    pub fn wall_texture(&self) -> &str {
+        &self.wall_texture
+    }
+
+    pub fn set_wall_texture(&mut self, wall_texture: String) {
+        self.wall_texture = wall_texture;
+    }
+
+    pub fn floor_texture(&self) -> &str {
+        &self.floor_texture
+    }
+
+    pub fn ceiling_texture(&self) -> &str {
+        &self.ceiling_texture
+    }
 }
@@ -1 +1,460 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use image::ImageReader;
+use nalgebra::Vector2;
+
+use super::texture::{Texture, DEFAULT_TEXTURE_SIZE};
+use super::Map;
+use crate::image::{convert_image_cut, OutputImageFormat};
+use crate::Result;
+
+/// A `BMAP name, <file>;` definition, optionally cut out of the file with `x, y, dx, dy`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    file: String,
+    rect: Option<[u32; 4]>,
+}
+
+impl Bitmap {
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn rect(&self) -> Option<[u32; 4]> {
+        self.rect
+    }
+}
+
+/// A `TEXTURE name { BMAPS ...; SCALE_XY x, y; }` definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDef {
+    bitmap: String,
+    scale: Vector2<f32>,
+}
+
+impl TextureDef {
+    /// The first bitmap of the texture, animation frames are ignored
+    pub fn bitmap(&self) -> &str {
+        &self.bitmap
+    }
+
+    pub fn scale(&self) -> Vector2<f32> {
+        self.scale
+    }
+}
+
+/// The texture definitions of one or more WDL scripts.
+///
+/// Only the parts needed to texture a level are read, every other statement is skipped.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Wdl {
+    bitmaps: BTreeMap<String, Bitmap>,
+    textures: BTreeMap<String, TextureDef>,
+    walls: BTreeMap<String, String>,
+    regions: BTreeMap<String, (String, String)>,
+    ceiling_attached: BTreeSet<String>,
+}
+
+/// A statement and the block following it, if any
+#[derive(Debug)]
+struct Statement {
+    words: Vec<String>,
+    block: Vec<Statement>,
+}
+
+impl Statement {
+    fn keyword(&self) -> String {
+        self.words
+            .first()
+            .map_or(String::new(), |w| w.to_uppercase())
+    }
+
+    /// Arguments after the keyword, without the commas between them
+    fn args(&self) -> Vec<&str> {
+        self.words
+            .iter()
+            .skip(1)
+            .map(String::as_str)
+            .filter(|w| *w != ",")
+            .collect()
+    }
+}
+
+/// Split a script into words, punctuation and `<file>` names, dropping comments
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '<' | '"' => {
+                let end = if c == '<' { '>' } else { '"' };
+                let mut token = c.to_string();
+                token.extend(chars.by_ref().take_while(|&c| c != end));
+                token.push(end);
+                tokens.push(token);
+            }
+            '{' | '}' | ';' | ',' => tokens.push(c.to_string()),
+            c => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};,<\"".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Group tokens into statements up to the end of the current block
+fn statements(tokens: &mut impl Iterator<Item = String>) -> Vec<Statement> {
+    let mut result = Vec::new();
+    let mut words = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            ";" => {
+                if !words.is_empty() {
+                    result.push(Statement {
+                        words: std::mem::take(&mut words),
+                        block: Vec::new(),
+                    });
+                }
+            }
+            "{" => result.push(Statement {
+                words: std::mem::take(&mut words),
+                block: statements(tokens),
+            }),
+            "}" => break,
+            _ => words.push(token),
+        }
+    }
+
+    if !words.is_empty() {
+        result.push(Statement {
+            words,
+            block: Vec::new(),
+        });
+    }
+    result
+}
+
+/// Argument of a statement inside a block, like `TEXTURE brick` in a wall definition
+fn block_arg<'a>(block: &'a [Statement], keyword: &str) -> Option<&'a str> {
+    block
+        .iter()
+        .find(|s| s.keyword() == keyword)
+        .and_then(|s| s.args().first().copied())
+}
+
+/// Find a file in a directory, ignoring case since scripts rarely match the archive
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+    if path.is_file() {
+        return Some(path);
+    }
+
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .is_some_and(|f| f.to_string_lossy().eq_ignore_ascii_case(name))
+        })
+}
+
+impl Wdl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the definitions of a WDL file, adding to the ones already read
+    pub fn parse_wdl(&mut self, filename: &Path) -> Result<()> {
+        let bytes = fs::read(filename)?;
+        self.parse_wdl_str(&String::from_utf8_lossy(&bytes));
+        Ok(())
+    }
+
+    /// Read the definitions of a WDL script, adding to the ones already read
+    pub fn parse_wdl_str(&mut self, text: &str) {
+        for statement in statements(&mut tokenize(text).into_iter()) {
+            let args = statement.args();
+            let Some(&name) = args.first() else {
+                continue;
+            };
+
+            match statement.keyword().as_str() {
+                "BMAP" => {
+                    let Some(file) = args.get(1) else {
+                        continue;
+                    };
+                    let rect: Vec<u32> = args[2..].iter().filter_map(|a| a.parse().ok()).collect();
+                    let bitmap = Bitmap {
+                        file: file.trim_matches(['<', '>']).to_string(),
+                        rect: rect.try_into().ok(),
+                    };
+                    self.bitmaps.insert(name.to_string(), bitmap);
+                }
+                "TEXTURE" => {
+                    let Some(bitmap) = block_arg(&statement.block, "BMAPS") else {
+                        continue;
+                    };
+                    let scale = statement
+                        .block
+                        .iter()
+                        .find(|s| s.keyword() == "SCALE_XY")
+                        .map(|s| {
+                            let scale: Vec<f32> =
+                                s.args().iter().filter_map(|a| a.parse().ok()).collect();
+                            match scale[..] {
+                                [x, y, ..] => Vector2::new(x, y),
+                                [xy] => Vector2::repeat(xy),
+                                [] => Vector2::repeat(1.0),
+                            }
+                        })
+                        .unwrap_or(Vector2::repeat(1.0));
+
+                    let texture = TextureDef {
+                        bitmap: bitmap.to_string(),
+                        scale,
+                    };
+                    self.textures.insert(name.to_string(), texture);
+                }
+                "WALL" => {
+                    if let Some(texture) = block_arg(&statement.block, "TEXTURE") {
+                        self.walls.insert(name.to_string(), texture.to_string());
+                    }
+                }
+                "REGION" => {
+                    let floor = block_arg(&statement.block, "FLOOR_TEX").unwrap_or_default();
+                    let ceiling = block_arg(&statement.block, "CEIL_TEX").unwrap_or_default();
+                    self.regions
+                        .insert(name.to_string(), (floor.to_string(), ceiling.to_string()));
+                }
+                "THING" | "ACTOR" => {
+                    let ceiling_attached = statement.block.iter().any(|s| {
+                        s.keyword() == "FLAGS"
+                            && s.args()
+                                .iter()
+                                .any(|f| f.eq_ignore_ascii_case("CEIL_ATTACHED"))
+                    });
+                    if ceiling_attached {
+                        self.ceiling_attached.insert(name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn bitmap(&self, name: &str) -> Option<&Bitmap> {
+        self.bitmaps.get(name)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&TextureDef> {
+        self.textures.get(name)
+    }
+
+    /// Texture of a wall definition
+    pub fn wall_texture(&self, wall: &str) -> Option<&str> {
+        self.walls.get(wall).map(String::as_str)
+    }
+
+    /// Floor and ceiling textures of a region definition
+    pub fn region_textures(&self, region: &str) -> Option<(&str, &str)> {
+        self.regions
+            .get(region)
+            .map(|(floor, ceiling)| (floor.as_str(), ceiling.as_str()))
+    }
+
+    /// Whether a thing or actor definition hangs from the ceiling
+    pub fn is_ceiling_attached(&self, name: &str) -> bool {
+        self.ceiling_attached.contains(name)
+    }
+
+    /// Size and scale of a texture, reading the size of its bitmap from `image_dir` when the
+    /// definition doesn't give one
+    pub fn resolve_texture(&self, name: &str, image_dir: &Path) -> Option<Texture> {
+        let definition = self.texture(name)?;
+        let bitmap = self.bitmap(definition.bitmap())?;
+        let path = find_file(image_dir, bitmap.file());
+
+        let stem = path
+            .as_deref()
+            .unwrap_or(Path::new(bitmap.file()))
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let scale = definition.scale();
+
+        // Cut out bitmaps become images of their own, the file can hold several of them
+        if let Some(rect @ [x, y, dx, dy]) = bitmap.rect() {
+            let image = format!("{}_{}_{}_{}_{}", stem, x, y, dx, dy);
+            let texture = Texture::new(image, dx as f32, dy as f32)
+                .with_cut(bitmap.file().to_string(), rect)
+                .with_scale(scale.x, scale.y);
+            return Some(texture);
+        }
+
+        let (width, height) = path
+            .and_then(|path| ImageReader::open(path).ok()?.with_guessed_format().ok())
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or((DEFAULT_TEXTURE_SIZE as u32, DEFAULT_TEXTURE_SIZE as u32));
+
+        Some(Texture::new(stem, width as f32, height as f32).with_scale(scale.x, scale.y))
+    }
+}
+
+impl Map {
+    /// Assign the textures of the WDL definitions to the walls and regions using them.
+    ///
+    /// Bitmaps are looked for in `image_dir` to find their size, missing ones keep the
+    /// default size.
+    pub fn apply_wdl(&mut self, wdl: &Wdl, image_dir: &Path) {
+        for wall in &mut self.walls {
+            if let Some(texture) = wdl.wall_texture(wall.name()) {
+                wall.set_wall_texture(texture.to_string());
+            }
+        }
+
+        for region in &mut self.regions {
+            if let Some((floor, ceiling)) = wdl.region_textures(region.name()) {
+                region.set_textures(floor.to_string(), ceiling.to_string());
+            }
+        }
+
+        for name in wdl.textures.keys() {
+            if let Some(texture) = wdl.resolve_texture(name, image_dir) {
+                self.textures.insert(name.clone(), texture);
+            }
+        }
+    }
+
+    /// Convert the textures cut out of larger bitmaps into images of their own, the rest are
+    /// converted along with the other image files
+    pub fn convert_cut_textures(
+        &self,
+        image_dir: &Path,
+        output_dir: &Path,
+        output_format: OutputImageFormat,
+    ) -> Result<()> {
+        for texture in self.textures.values() {
+            let Some((file, rect)) = texture.cut() else {
+                continue;
+            };
+            if let Some(path) = find_file(image_dir, file) {
+                convert_image_cut(&path, rect, output_dir, texture.image(), output_format)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Region, VertexId, Wall};
+
+    const TEST_WDL: &str = "# Test script
+BMAP brick_map, <brick.pcx>;
+BMAP stone_map, <tiles.pcx>, 0, 64, 32, 16; // cut out
+TEXTURE brick_tex {
+    BMAPS brick_map, brick_map;
+    SCALE_XY 2, 0.5;
+}
+TEXTURE stone_tex { BMAPS stone_map; }
+/* A wall
+   and a region */
+WALL brick { TEXTURE brick_tex; }
+REGION hall {
+    FLOOR_TEX stone_tex;
+    CEIL_TEX brick_tex;
+    FLOOR_HGT 0;
+}
+THING lamp { FLAGS PASSABLE, CEIL_ATTACHED; }
+ACTOR guard { FLAGS PASSABLE; }
+SKILL health { VAL 100; }
+";
+
+    #[test]
+    fn test_parse() {
+        let mut wdl = Wdl::new();
+        wdl.parse_wdl_str(TEST_WDL);
+
+        assert_eq!(wdl.bitmap("brick_map").unwrap().file(), "brick.pcx");
+        assert_eq!(
+            wdl.bitmap("stone_map").unwrap().rect(),
+            Some([0, 64, 32, 16])
+        );
+        assert_eq!(wdl.texture("brick_tex").unwrap().bitmap(), "brick_map");
+        assert_eq!(
+            wdl.texture("brick_tex").unwrap().scale(),
+            Vector2::new(2.0, 0.5)
+        );
+        assert_eq!(wdl.wall_texture("brick"), Some("brick_tex"));
+        assert_eq!(
+            wdl.region_textures("hall"),
+            Some(("stone_tex", "brick_tex"))
+        );
+        assert_eq!(wdl.region_textures("health"), None);
+        assert!(wdl.is_ceiling_attached("lamp"));
+        assert!(!wdl.is_ceiling_attached("guard"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut wdl = Wdl::new();
+        wdl.parse_wdl_str(TEST_WDL);
+
+        let mut map = Map::new("test".to_string());
+        let hall = map.add_region(Region::new("hall".to_string(), 0.0, 64.0));
+        map.add_wall(Wall::new(
+            "brick".to_string(),
+            VertexId(0),
+            VertexId(1),
+            hall,
+            hall,
+        ));
+        map.apply_wdl(&wdl, Path::new("/nonexistent"));
+
+        assert_eq!(map.walls()[0].wall_texture(), "brick_tex");
+        assert_eq!(map.regions()[0].floor_texture(), "stone_tex");
+
+        // The cut out becomes an image of its own, so it tiles on its own
+        let stone = map.texture("stone_tex").unwrap();
+        assert_eq!(stone.image(), "tiles_0_64_32_16");
+        assert_eq!(stone.cut(), Some(("tiles.pcx", [0, 64, 32, 16])));
+        assert_eq!(stone.size(), Vector2::new(32.0, 16.0));
+
+        // The bitmap is missing, so only the scale is known
+        let brick = map.texture("brick_tex").unwrap();
+        assert_eq!(brick.world_size(), Vector2::new(128.0, 32.0));
+    }
+}
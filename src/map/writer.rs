@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{Map, ObjectType};
+use crate::{Error, Result};
+
+/// Names are single tokens in a WMP line, anything the parser would split on is rejected
+fn check_name(kind: &str, index: usize, name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('#')
+        || name.contains(|c: char| c.is_whitespace() || c == ';')
+    {
+        return Err(Error::MapWrite(format!(
+            "{} {} has an invalid name {:?}",
+            kind, index, name
+        )));
+    }
+    Ok(())
+}
+
+/// NaN and infinities can't be read back, the parser only takes plain numbers
+fn check_numbers(kind: &str, index: usize, values: &[f32]) -> Result<()> {
+    if let Some(value) = values.iter().find(|v| !v.is_finite()) {
+        return Err(Error::MapWrite(format!(
+            "{} {} has an invalid number {}",
+            kind, index, value
+        )));
+    }
+    Ok(())
+}
+
+impl Map {
+    /// Save the map as a WMP file
+    pub fn write_wmp(&self, filename: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write_wmp_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Write the map in the WMP format, vertices, regions, walls and objects in that order
+    /// since walls and objects refer to the earlier lists by index.
+    pub fn write_wmp_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            check_numbers("Vertex", index, vertex.as_slice())?;
+            writeln!(writer, "VERTEX {} {} {};", vertex.x, vertex.y, vertex.z)?;
+        }
+
+        for (index, region) in self.regions.iter().enumerate() {
+            check_name("Region", index, region.name())?;
+            check_numbers(
+                "Region",
+                index,
+                &[region.floor_height(), region.ceiling_height()],
+            )?;
+            writeln!(
+                writer,
+                "REGION {} {} {};",
+                region.name(),
+                region.floor_height(),
+                region.ceiling_height()
+            )?;
+        }
+
+        for (index, wall) in self.walls.iter().enumerate() {
+            check_name("Wall", index, wall.name())?;
+            check_numbers("Wall", index, &[wall.offset_x(), wall.offset_y()])?;
+            writeln!(
+                writer,
+                "WALL {} {} {} {} {} {} {};",
+                wall.name(),
+                wall.vertex1_index(),
+                wall.vertex2_index(),
+                wall.region1_index(),
+                wall.region2_index(),
+                wall.offset_x(),
+                wall.offset_y()
+            )?;
+        }
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let keyword = object.object_type().keyword();
+            let position = object.position();
+            check_numbers("Object", index, &[position.x, position.y, object.angle()])?;
+
+            // The player start has no name of its own
+            if let ObjectType::PlayerStart = object.object_type() {
+                writeln!(
+                    writer,
+                    "{} {} {} {} {};",
+                    keyword,
+                    position.x,
+                    position.y,
+                    object.angle(),
+                    object.region()
+                )?;
+            } else {
+                check_name("Object", index, object.name())?;
+                writeln!(
+                    writer,
+                    "{} {} {} {} {} {};",
+                    keyword,
+                    object.name(),
+                    position.x,
+                    position.y,
+                    object.angle(),
+                    object.region()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The map in the WMP format
+    pub fn to_wmp_string(&self) -> Result<String> {
+        let mut output = Vec::new();
+        self.write_wmp_to(&mut output)?;
+
+        Ok(String::from_utf8(output).expect("WMP output is built from strings"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{ParseMode, Region};
+
+    const TEST_MAP: &str = "VERTEX 0 0 0;
+VERTEX 64.25 -0.1 0;
+VERTEX 64 64 16;
+REGION hall 0 128;
+REGION pit -32.5 128;
+WALL stone 0 1 0 1 0.5 8;
+WALL stone 1 2 1 0 0 0;
+THING lamp 10 10 1.5707964 0;
+ACTOR guard 20 30 3.1415927 1;
+PLAYER_START 5 5 0 0;
+";
+
+    fn parse(text: &str) -> Map {
+        let mut map = Map::default();
+        let warnings = map
+            .parse_wmp_str(Path::new("test.wmp"), text, ParseMode::Strict)
+            .unwrap();
+        assert!(warnings.is_empty());
+        map
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map = parse(TEST_MAP);
+        let written = map.to_wmp_string().unwrap();
+
+        assert_eq!(written, TEST_MAP);
+        assert_eq!(parse(&written), map);
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let mut map = parse(TEST_MAP);
+        map.regions[1] = Region::new("two words".to_string(), 0.0, 128.0);
+
+        assert!(matches!(map.to_wmp_string(), Err(Error::MapWrite(_))));
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let mut map = parse(TEST_MAP);
+        map.vertices[1].y = f32::NAN;
+        assert!(matches!(map.to_wmp_string(), Err(Error::MapWrite(_))));
+
+        let mut map = parse(TEST_MAP);
+        map.regions[0] = Region::new("hall".to_string(), 0.0, f32::INFINITY);
+        assert!(matches!(map.to_wmp_string(), Err(Error::MapWrite(_))));
+    }
+}
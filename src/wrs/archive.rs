@@ -0,0 +1,338 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom, Take},
+    path::Path,
+};
+
+use super::lzss::{unlzss_with, LzssParams, LzssReader};
+use crate::{Error, Result};
+
+/// Size of the fixed entry header: 13 byte name, compressed size and uncompressed size
+pub const ENTRY_HEADER_SIZE: u64 = 13 + 4 + 4;
+
+/// A single file stored in a WRS archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrsEntry {
+    name: String,
+    raw_name: [u8; 13],
+    header_offset: u64,
+    compressed_size: usize,
+    size: usize,
+}
+
+impl WrsEntry {
+    /// The entry name as stored in the archive, up to the first NUL byte
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name field exactly as stored in the archive
+    pub fn raw_name(&self) -> &[u8; 13] {
+        &self.raw_name
+    }
+
+    /// Offset of the entry header from the start of the archive
+    pub fn header_offset(&self) -> u64 {
+        self.header_offset
+    }
+
+    /// Offset of the compressed data from the start of the archive
+    pub fn data_offset(&self) -> u64 {
+        self.header_offset + ENTRY_HEADER_SIZE
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        self.compressed_size
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// A WRS archive which has been indexed but not extracted.
+///
+/// Entries are decompressed on demand, nothing is written to disk.
+#[derive(Debug)]
+pub struct WrsArchive<R> {
+    reader: R,
+    entries: Vec<WrsEntry>,
+    params: LzssParams,
+    archive_size: u64,
+    index_end: u64,
+}
+
+impl WrsArchive<BufReader<File>> {
+    /// Open and index an archive on disk
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> WrsArchive<R> {
+    /// Index an archive from any seekable reader
+    pub fn new(mut reader: R) -> Result<Self> {
+        let asize = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut entries = Vec::new();
+        let mut offset: u64 = 0;
+
+        while offset + ENTRY_HEADER_SIZE <= asize {
+            // Read the entry header
+            let mut header = [0u8; ENTRY_HEADER_SIZE as usize];
+            reader.read_exact(&mut header)?;
+
+            let raw_name: [u8; 13] = header[..13].try_into().unwrap();
+            // Anything after the first NUL is left over padding
+            let name_len = raw_name
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap_or(raw_name.len());
+            let name = String::from_utf8_lossy(&raw_name[..name_len]).to_string();
+            let compressed_size = u32::from_be_bytes(header[13..17].try_into().unwrap()) as usize;
+            let size = u32::from_be_bytes(header[17..21].try_into().unwrap()) as usize;
+
+            entries.push(WrsEntry {
+                name,
+                raw_name,
+                header_offset: offset,
+                compressed_size,
+                size,
+            });
+
+            // Skip over the compressed data to the next header
+            offset += ENTRY_HEADER_SIZE + compressed_size as u64;
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+
+        Ok(Self {
+            reader,
+            entries,
+            params: LzssParams::default(),
+            archive_size: asize,
+            index_end: offset,
+        })
+    }
+
+    /// Decompress entries with different LZSS parameters
+    pub fn with_params(mut self, params: LzssParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn params(&self) -> &LzssParams {
+        &self.params
+    }
+
+    /// Size of the archive in bytes
+    pub fn size(&self) -> u64 {
+        self.archive_size
+    }
+
+    /// Bytes after the last entry which are too short to be an entry header
+    pub fn trailing_bytes(&self) -> u64 {
+        self.archive_size.saturating_sub(self.index_end)
+    }
+
+    /// Bytes of the last entry's data missing from the end of the archive
+    pub fn missing_bytes(&self) -> u64 {
+        self.index_end.saturating_sub(self.archive_size)
+    }
+
+    /// All entries in archive order
+    pub fn entries(&self) -> &[WrsEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find an entry by name, names are matched case-insensitively like DOS
+    pub fn entry(&self, name: &str) -> Option<&WrsEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Read the compressed bytes of an entry
+    pub fn read_raw(&mut self, entry: &WrsEntry) -> Result<Vec<u8>> {
+        // Sizes come from the header, check them before allocating
+        if entry.data_offset() + entry.compressed_size as u64 > self.archive_size {
+            return Err(Error::Archive(format!(
+                "{} runs past the end of the archive",
+                entry.name
+            )));
+        }
+        self.reader.seek(SeekFrom::Start(entry.data_offset()))?;
+
+        let mut compressed_data = vec![0u8; entry.compressed_size];
+        self.reader.read_exact(&mut compressed_data)?;
+
+        Ok(compressed_data)
+    }
+
+    /// Read and decompress an entry
+    pub fn read_entry(&mut self, entry: &WrsEntry) -> Result<Vec<u8>> {
+        let compressed_data = self.read_raw(entry)?;
+
+        if entry.size > self.params.max_output(compressed_data.len()) {
+            return Err(Error::Compression(format!(
+                "{} declares {} bytes, more than its stream can hold",
+                entry.name, entry.size
+            )));
+        }
+
+        let mut decompressed_data = vec![0u8; entry.size];
+        let size = unlzss_with(&compressed_data, &mut decompressed_data, &self.params)?;
+        if size != entry.size {
+            return Err(Error::Compression(format!(
+                "{} decompressed to {} of {} bytes",
+                entry.name, size, entry.size
+            )));
+        }
+
+        Ok(decompressed_data)
+    }
+
+    /// Stream an entry, the data is decompressed as it is read
+    pub fn entry_reader(&mut self, entry: &WrsEntry) -> Result<Take<LzssReader<Take<&mut R>>>> {
+        self.reader.seek(SeekFrom::Start(entry.data_offset()))?;
+
+        let compressed = (&mut self.reader).take(entry.compressed_size as u64);
+        Ok(LzssReader::with_params(compressed, &self.params).take(entry.size as u64))
+    }
+
+    /// Read and decompress an entry by name
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>> {
+        let entry = self.find(name)?;
+        self.read_entry(&entry)
+    }
+
+    /// Open an entry by name as a reader over the decompressed data
+    pub fn open_entry(&mut self, name: &str) -> Result<impl Read + '_> {
+        let entry = self.find(name)?;
+        self.entry_reader(&entry)
+    }
+
+    fn find(&self, name: &str) -> Result<WrsEntry> {
+        self.entry(name)
+            .cloned()
+            .ok_or_else(|| Error::Archive(format!("Entry not found: {}", name)))
+    }
+
+    /// Iterate over all entries in archive order along with their decompressed data
+    pub fn files(&mut self) -> impl Iterator<Item = Result<(WrsEntry, Vec<u8>)>> + '_ {
+        let entries = self.entries.clone();
+        entries.into_iter().map(move |entry| {
+            let data = self.read_entry(&entry)?;
+            Ok((entry, data))
+        })
+    }
+
+    pub(crate) fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn push_entry(archive: &mut Vec<u8>, name: &str, compressed: &[u8], size: usize) {
+        let mut name_bytes = [0u8; 13];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        archive.extend_from_slice(&name_bytes);
+        archive.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+        archive.extend_from_slice(&(size as u32).to_be_bytes());
+        archive.extend_from_slice(compressed);
+    }
+
+    // Every flag bit set means every following byte is a literal
+    fn test_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+        push_entry(
+            &mut archive,
+            "HELLO.TXT",
+            &[0xff, b'H', b'E', b'L', b'L', b'O'],
+            5,
+        );
+        push_entry(&mut archive, "AB.WDL", &[0xff, b'A', b'B'], 2);
+        archive
+    }
+
+    #[test]
+    fn test_index() {
+        let archive = WrsArchive::new(Cursor::new(test_archive())).unwrap();
+
+        assert_eq!(archive.len(), 2);
+        let entries = archive.entries();
+        assert_eq!(entries[0].name(), "HELLO.TXT");
+        assert_eq!(entries[0].header_offset(), 0);
+        assert_eq!(entries[0].compressed_size(), 6);
+        assert_eq!(entries[0].size(), 5);
+        assert_eq!(entries[1].name(), "AB.WDL");
+        assert_eq!(entries[1].header_offset(), ENTRY_HEADER_SIZE + 6);
+
+        // Bytes after the terminating NUL are not part of the name
+        let mut data = Vec::new();
+        push_entry(&mut data, "B.TXT\0\0\0\0\0X", &[0xff, b'B'], 1);
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.entries()[0].name(), "B.TXT");
+        assert_eq!(archive.read("b.txt").unwrap(), b"B");
+    }
+
+    #[test]
+    fn test_read() {
+        let mut archive = WrsArchive::new(Cursor::new(test_archive())).unwrap();
+
+        assert_eq!(archive.read("hello.txt").unwrap(), b"HELLO");
+        assert_eq!(archive.read("AB.WDL").unwrap(), b"AB");
+        assert!(matches!(archive.read("MISSING"), Err(Error::Archive(_))));
+
+        let mut contents = String::new();
+        archive
+            .open_entry("ab.wdl")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "AB");
+
+        let names: Vec<String> = archive
+            .files()
+            .map(|f| f.unwrap().0.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["HELLO.TXT", "AB.WDL"]);
+
+        // Declared sizes the stream doesn't match are errors rather than zero filled
+        let mut data = test_archive();
+        data[20] = 7;
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        assert!(matches!(
+            archive.read("HELLO.TXT"),
+            Err(Error::Compression(_))
+        ));
+        let mut data = test_archive();
+        data[17..21].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        assert!(matches!(
+            archive.read("HELLO.TXT"),
+            Err(Error::Compression(_))
+        ));
+        let mut data = test_archive();
+        data.truncate(data.len() - 1);
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        assert!(matches!(archive.read("AB.WDL"), Err(Error::Archive(_))));
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+use super::archive::{WrsArchive, WrsEntry};
+use super::filter::EntryFilter;
+use super::lzss::{LzssParams, LzssReader};
+use super::names::{collision_key, renamed, sanitize_name};
+use crate::{Error, Result};
+
+/// What to do when an entry would overwrite a file written earlier in the same extraction.
+/// Files that were in the output directory before are overwritten, so extracting again
+/// replaces the earlier output.
+///
+/// Names are compared case-insensitively, as DOS and most desktop file systems do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, ValueEnum)]
+pub enum CollisionPolicy {
+    /// Write the entry under a new name, e.g. `WALL~1.PCX`
+    #[default]
+    Rename,
+    /// Keep the first file and skip the entry
+    Skip,
+    /// Stop extracting
+    Error,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExtractStatus {
+    Extracted,
+    Renamed,
+    Skipped,
+    /// The entry was selected but writing it failed
+    Failed,
+}
+
+impl ExtractStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtractStatus::Extracted => "extracted",
+            ExtractStatus::Renamed => "renamed",
+            ExtractStatus::Skipped => "skipped",
+            ExtractStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Record of an extracted entry, keeping the name exactly as it was stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    archive: PathBuf,
+    entry: WrsEntry,
+    output_name: String,
+    status: ExtractStatus,
+}
+
+impl ManifestEntry {
+    pub fn archive(&self) -> &Path {
+        &self.archive
+    }
+
+    pub fn entry(&self) -> &WrsEntry {
+        &self.entry
+    }
+
+    /// Name of the file written to the output directory
+    pub fn output_name(&self) -> &str {
+        &self.output_name
+    }
+
+    pub fn status(&self) -> ExtractStatus {
+        self.status
+    }
+}
+
+/// A single entry to decompress, independent of every other entry
+#[derive(Debug)]
+struct ExtractJob {
+    archive: PathBuf,
+    entry: WrsEntry,
+    output_file: PathBuf,
+    // Position of the entry in the extractor's manifest
+    manifest_index: usize,
+}
+
+impl ExtractJob {
+    /// Decompress straight into the output file, returns a warning if the size is wrong
+    fn run(&self, params: &LzssParams) -> Result<Option<String>> {
+        // Every job has its own handle so entries can be read concurrently
+        let mut input = BufReader::new(File::open(&self.archive)?);
+        input.seek(SeekFrom::Start(self.entry.data_offset()))?;
+
+        let compressed = input.take(self.entry.compressed_size() as u64);
+        let mut reader = LzssReader::with_params(compressed, params).take(self.entry.size() as u64);
+
+        let mut output_file = BufWriter::new(File::create(&self.output_file)?);
+        let written = io::copy(&mut reader, &mut output_file)?;
+        output_file.flush()?;
+
+        if written != self.entry.size() as u64 {
+            return Ok(Some(format!(
+                "{} decompressed to {} of {} bytes",
+                self.entry.name(),
+                written,
+                self.entry.size()
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Quote a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Extracts archives to disk, keeping track of every file written so names don't collide
+#[derive(Debug, Default)]
+pub struct Extractor {
+    params: LzssParams,
+    filter: EntryFilter,
+    collisions: CollisionPolicy,
+    written: HashSet<(PathBuf, String)>,
+    manifest: Vec<ManifestEntry>,
+}
+
+impl Extractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_params(mut self, params: LzssParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: EntryFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_collisions(mut self, collisions: CollisionPolicy) -> Self {
+        self.collisions = collisions;
+        self
+    }
+
+    /// Every entry handled so far, in extraction order
+    pub fn manifest(&self) -> &[ManifestEntry] {
+        &self.manifest
+    }
+
+    /// Pick the output name for an entry, `None` if it should be skipped
+    fn output_name(&mut self, output_path: &Path, entry: &WrsEntry) -> Result<Option<String>> {
+        let name = sanitize_name(entry.raw_name())?;
+        let key = |name: &str| (output_path.to_path_buf(), collision_key(name));
+
+        if self.written.insert(key(&name)) {
+            return Ok(Some(name));
+        }
+
+        match self.collisions {
+            CollisionPolicy::Rename => {
+                let name = (1..)
+                    .map(|n| renamed(&name, n))
+                    .find(|n| !self.written.contains(&key(n)))
+                    .unwrap();
+                self.written.insert(key(&name));
+                Ok(Some(name))
+            }
+            CollisionPolicy::Skip => Ok(None),
+            CollisionPolicy::Error => Err(Error::Archive(format!(
+                "Entry name collides with an extracted file: {}",
+                name
+            ))),
+        }
+    }
+
+    /// Extract the selected entries of an archive, returns the number of files written
+    pub fn extract<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        input_path: P,
+        output_path: Q,
+    ) -> Result<usize> {
+        self.extract_many(&[(input_path, output_path)])
+            .pop()
+            .unwrap()
+    }
+
+    /// Extract several archives, returning the number of files written for each.
+    ///
+    /// Entries from all archives are decompressed in parallel on the current rayon thread pool.
+    /// Names, the manifest and log messages are handled in archive order, so the results don't
+    /// depend on scheduling.
+    pub fn extract_many<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        archives: &[(P, Q)],
+    ) -> Vec<Result<usize>> {
+        let mut results = Vec::with_capacity(archives.len());
+        let mut jobs = Vec::new();
+
+        for (index, (input_path, output_path)) in archives.iter().enumerate() {
+            // Nothing of an archive that fails to plan gets written, so it leaves no record
+            let manifest_len = self.manifest.len();
+            let written = self.written.clone();
+            match self.plan(input_path.as_ref(), output_path.as_ref()) {
+                Ok(archive_jobs) => {
+                    results.push(Ok(0));
+                    jobs.extend(archive_jobs.into_iter().map(|job| (index, job)));
+                }
+                Err(e) => {
+                    self.manifest.truncate(manifest_len);
+                    self.written = written;
+                    results.push(Err(e));
+                }
+            }
+        }
+
+        let params = self.params;
+        let outcomes: Vec<(usize, usize, Result<Option<String>>)> = jobs
+            .par_iter()
+            .map(|(index, job)| (*index, job.manifest_index, job.run(&params)))
+            .collect();
+
+        for (index, manifest_index, outcome) in outcomes {
+            match outcome {
+                Ok(warning) => {
+                    if let Some(warning) = warning {
+                        eprintln!("Extraction Error: {}", warning);
+                    }
+                    if let Ok(file_count) = &mut results[index] {
+                        *file_count += 1;
+                    }
+                }
+                Err(e) => {
+                    self.manifest[manifest_index].status = ExtractStatus::Failed;
+                    if results[index].is_ok() {
+                        results[index] = Err(e);
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Index an archive and pick output names for the selected entries
+    fn plan(&mut self, input_path: &Path, output_path: &Path) -> Result<Vec<ExtractJob>> {
+        let archive = WrsArchive::open(input_path)?;
+        let entries: Vec<WrsEntry> = archive
+            .entries()
+            .iter()
+            .filter(|e| self.filter.matches(e.name()))
+            .cloned()
+            .collect();
+        let mut jobs = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let output_name = match self.output_name(output_path, &entry) {
+                Ok(name) => name,
+                Err(e @ Error::UnsafeName(_)) => {
+                    // Unsafe names are always skipped, whatever the collision policy
+                    eprintln!("Extraction Error: {}", e);
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+
+            let Some(output_name) = output_name else {
+                self.manifest.push(ManifestEntry {
+                    archive: input_path.to_path_buf(),
+                    entry,
+                    output_name: String::new(),
+                    status: ExtractStatus::Skipped,
+                });
+                continue;
+            };
+
+            let status = if collision_key(&output_name) == collision_key(entry.name()) {
+                ExtractStatus::Extracted
+            } else {
+                ExtractStatus::Renamed
+            };
+            jobs.push(ExtractJob {
+                archive: input_path.to_path_buf(),
+                entry: entry.clone(),
+                output_file: output_path.join(&output_name),
+                manifest_index: self.manifest.len(),
+            });
+            self.manifest.push(ManifestEntry {
+                archive: input_path.to_path_buf(),
+                entry,
+                output_name,
+                status,
+            });
+        }
+
+        if archive.missing_bytes() > 0 {
+            eprintln!(
+                "Extraction Error: archive is truncated, {} bytes missing",
+                archive.missing_bytes()
+            );
+        }
+        if archive.trailing_bytes() > 0 {
+            eprintln!(
+                "Extraction Error: archive is truncated, the last entry header has {} bytes",
+                archive.trailing_bytes()
+            );
+        }
+
+        Ok(jobs)
+    }
+
+    /// Creates a CSV string from the manifest, raw names are hex encoded
+    pub fn create_manifest_csv(&self) -> String {
+        let mut output = Vec::new();
+
+        output.push(
+            "archive,raw_name,name,output_name,status,offset,compressed_size,size".to_string(),
+        );
+        for m in &self.manifest {
+            let raw_name: String = m
+                .entry
+                .raw_name()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+
+            output.push(format!(
+                "{},{},{},{},{},{},{},{}",
+                csv_field(&m.archive.to_string_lossy()),
+                raw_name,
+                csv_field(m.entry.name()),
+                csv_field(&m.output_name),
+                m.status.as_str(),
+                m.entry.header_offset(),
+                m.entry.compressed_size(),
+                m.entry.size()
+            ));
+        }
+
+        output.join("\n")
+    }
+
+    pub fn write_manifest<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", self.create_manifest_csv())?;
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrs::WrsWriter;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("a3conv_{}_{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("out")).unwrap();
+        dir
+    }
+
+    fn write_archive(path: &Path, names: &[&[u8]]) {
+        // The writer only accepts sane names, so patch raw names in afterwards
+        let mut writer = WrsWriter::new(Vec::new());
+        for (i, _) in names.iter().enumerate() {
+            writer.add(&format!("F{}", i), &[i as u8]).unwrap();
+        }
+        let mut data = writer.finish().unwrap();
+
+        let mut offset = 0;
+        for name in names {
+            data[offset..offset + 13].fill(0);
+            data[offset..offset + name.len()].copy_from_slice(name);
+            let zsize = u32::from_be_bytes(data[offset + 13..offset + 17].try_into().unwrap());
+            offset += 21 + zsize as usize;
+        }
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_collisions() {
+        let dir = test_dir("collisions");
+        let archive = dir.join("TEST.WRS");
+        write_archive(
+            &archive,
+            &[b"WALL.PCX", b"wall.pcx", b"../EVIL", b"SKY.PCX"],
+        );
+
+        let mut extractor = Extractor::new();
+        assert_eq!(extractor.extract(&archive, dir.join("out")).unwrap(), 3);
+        assert_eq!(fs::read(dir.join("out/WALL.PCX")).unwrap(), [0]);
+        assert_eq!(fs::read(dir.join("out/wall~1.pcx")).unwrap(), [1]);
+        assert!(!dir.join("EVIL").exists());
+
+        let statuses: Vec<ExtractStatus> =
+            extractor.manifest().iter().map(|m| m.status()).collect();
+        assert_eq!(
+            statuses,
+            [
+                ExtractStatus::Extracted,
+                ExtractStatus::Renamed,
+                ExtractStatus::Skipped,
+                ExtractStatus::Extracted
+            ]
+        );
+        assert!(extractor
+            .create_manifest_csv()
+            .contains(",2e2e2f4556494c000000000000,../EVIL,,skipped,"));
+
+        fs::create_dir_all(dir.join("skip")).unwrap();
+        fs::create_dir_all(dir.join("error")).unwrap();
+        let mut extractor = Extractor::new().with_collisions(CollisionPolicy::Skip);
+        assert_eq!(extractor.extract(&archive, dir.join("skip")).unwrap(), 2);
+
+        let mut extractor = Extractor::new().with_collisions(CollisionPolicy::Error);
+        assert!(extractor.extract(&archive, dir.join("error")).is_err());
+        assert!(extractor.manifest().is_empty());
+
+        // Unsafe names are skipped even when collisions are errors
+        let safe = dir.join("SAFE.WRS");
+        write_archive(&safe, &[b"WALL.PCX", b"../EVIL"]);
+        let mut extractor = Extractor::new().with_collisions(CollisionPolicy::Error);
+        assert_eq!(extractor.extract(&safe, dir.join("error")).unwrap(), 1);
+
+        // Extracting again overwrites the earlier output rather than renaming
+        let mut extractor = Extractor::new();
+        assert_eq!(extractor.extract(&safe, dir.join("out")).unwrap(), 1);
+        assert_eq!(extractor.manifest()[0].output_name(), "WALL.PCX");
+        assert_eq!(extractor.manifest()[0].status(), ExtractStatus::Extracted);
+
+        // Entries that can't be written are recorded as failed
+        let mut extractor = Extractor::new();
+        assert!(extractor.extract(&archive, dir.join("missing")).is_err());
+        assert!(extractor
+            .manifest()
+            .iter()
+            .all(|m| m.status() != ExtractStatus::Extracted));
+        assert_eq!(extractor.manifest()[0].status(), ExtractStatus::Failed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
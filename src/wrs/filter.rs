@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+
+use crate::{Error, Result};
+
+/// Matches entry names, always case-insensitively like DOS
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    /// Shell style wildcards, e.g. `*.PCX` or `WALL?.PCX`
+    Glob(Pattern),
+    Regex(Regex),
+    /// File extension without the dot
+    Extension(String),
+    /// An exact entry name
+    Name(String),
+}
+
+impl NamePattern {
+    /// Parse a pattern, `re:` selects a regex, `ext:` an extension, anything else is a glob
+    pub fn parse(pattern: &str) -> Result<Self> {
+        if let Some(regex) = pattern.strip_prefix("re:") {
+            let regex = RegexBuilder::new(regex)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| Error::Pattern(e.to_string()))?;
+            Ok(NamePattern::Regex(regex))
+        } else if let Some(extension) = pattern.strip_prefix("ext:") {
+            Ok(NamePattern::Extension(
+                extension.trim_start_matches('.').to_string(),
+            ))
+        } else if pattern.contains(['*', '?', '[']) {
+            let glob = Pattern::new(pattern).map_err(|e| Error::Pattern(e.to_string()))?;
+            Ok(NamePattern::Glob(glob))
+        } else {
+            Ok(NamePattern::Name(pattern.to_string()))
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Glob(glob) => glob.matches_with(
+                name,
+                MatchOptions {
+                    case_sensitive: false,
+                    ..Default::default()
+                },
+            ),
+            NamePattern::Regex(regex) => regex.is_match(name),
+            NamePattern::Extension(extension) => name
+                .rsplit_once('.')
+                .is_some_and(|(_, e)| e.eq_ignore_ascii_case(extension)),
+            NamePattern::Name(exact) => name.eq_ignore_ascii_case(exact),
+        }
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Selects archive entries by name.
+///
+/// With no include patterns every entry is included, exclude patterns always win.
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    include: Vec<NamePattern>,
+    exclude: Vec<NamePattern>,
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include(mut self, pattern: NamePattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    pub fn exclude(mut self, pattern: NamePattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    /// Include an explicit list of entry names
+    pub fn names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include
+            .extend(names.into_iter().map(|n| NamePattern::Name(n.into())));
+        self
+    }
+
+    /// True if the filter selects everything
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(name)))
+            && !self.exclude.iter().any(|p| p.matches(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> NamePattern {
+        NamePattern::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(pattern("*.pcx").matches("WALL.PCX"));
+        assert!(pattern("W?LL.*").matches("wall.wdl"));
+        assert!(!pattern("*.pcx").matches("WALL.WAV"));
+        assert!(pattern("re:^(wall|sky)[0-9]*\\.pcx$").matches("SKY12.PCX"));
+        assert!(pattern("ext:.wav").matches("DOOR.WAV"));
+        assert!(!pattern("ext:wav").matches("WAV"));
+        assert!(pattern("level.wmp").matches("LEVEL.WMP"));
+        assert!(NamePattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = EntryFilter::new()
+            .include(pattern("*.pcx"))
+            .include(pattern("ext:wmp"))
+            .exclude(pattern("SKY*"));
+
+        assert!(filter.matches("WALL.PCX"));
+        assert!(filter.matches("LEVEL.WMP"));
+        assert!(!filter.matches("SKY.PCX"));
+        assert!(!filter.matches("DOOR.WAV"));
+
+        let filter = EntryFilter::new().exclude(pattern("*.wav"));
+        assert!(filter.matches("WALL.PCX"));
+        assert!(!filter.matches("DOOR.WAV"));
+
+        let filter = EntryFilter::new().names(["LEVEL.WDL", "WALL.PCX"]);
+        assert!(filter.matches("wall.pcx"));
+        assert!(!filter.matches("SKY.PCX"));
+        assert!(EntryFilter::new().matches("ANYTHING"));
+    }
+}
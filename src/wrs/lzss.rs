@@ -1,19 +1,199 @@
-/// LZSS algorithm based on QuickBMS LZSS implementation
+//! LZSS algorithm based on QuickBMS LZSS implementation
+
+use crate::{Error, Result};
+use clap::ValueEnum;
+use std::io::{self, ErrorKind, Read};
+
+/// Known LZSS variants used by Acknex-era resource files
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, ValueEnum)]
+pub enum LzssPreset {
+    /// Acknex 3 WRS archives, same as the QuickBMS `lzss` defaults
+    #[default]
+    Acknex3,
+    /// QuickBMS `lzss0`, the window is filled with zeros instead of spaces
+    Lzss0,
+    /// A 1 KB window with 6 bit match lengths, used by related titles with the same container
+    SmallWindow,
+}
+
+impl LzssPreset {
+    pub fn params(&self) -> LzssParams {
+        match self {
+            LzssPreset::Acknex3 => LzssParams::default(),
+            LzssPreset::Lzss0 => LzssParams {
+                init_char: 0,
+                ..Default::default()
+            },
+            LzssPreset::SmallWindow => LzssParams {
+                ei: 10,
+                ej: 6,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Parameters of the LZSS stream, named after the QuickBMS script variables.
+///
+/// Use [`LzssParams::builder`] to customise them, the defaults match Acknex 3.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LzssParams {
+    ei: u32,
+    ej: u32,
+    p: u32,
+    rless: u32,
+    init_char: u8,
+}
+
+impl Default for LzssParams {
+    fn default() -> Self {
+        Self {
+            ei: 12,
+            ej: 4,
+            p: 2,
+            rless: 2,
+            init_char: 0x20,
+        }
+    }
+}
+
+impl LzssParams {
+    pub fn builder() -> LzssParamsBuilder {
+        LzssParamsBuilder {
+            params: Self::default(),
+        }
+    }
+
+    /// A builder starting from these parameters, to tweak a preset
+    pub fn to_builder(self) -> LzssParamsBuilder {
+        LzssParamsBuilder { params: self }
+    }
+
+    /// Number of bits for the window size
+    pub fn ei(&self) -> u32 {
+        self.ei
+    }
+
+    /// Number of bits for the match length
+    pub fn ej(&self) -> u32 {
+        self.ej
+    }
+
+    /// Matches shorter than or equal to this are stored as literals
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+
+    /// Distance of the initial write position from the end of the window
+    pub fn rless(&self) -> u32 {
+        self.rless
+    }
+
+    /// Byte the window is filled with before decoding
+    pub fn init_char(&self) -> u8 {
+        self.init_char
+    }
+
+    fn window_size(&self) -> usize {
+        1 << self.ei
+    }
+
+    fn length_size(&self) -> usize {
+        1 << self.ej
+    }
+
+    /// Most bytes a stream of `compressed_size` bytes can decode to, every two bytes are at
+    /// most one match
+    pub(crate) fn max_output(&self, compressed_size: usize) -> usize {
+        compressed_size.div_ceil(2) * (self.length_size() + self.p as usize)
+    }
+
+    fn start_index(&self) -> usize {
+        (self.window_size() - self.length_size()) - self.rless as usize
+    }
+}
+
+impl From<LzssPreset> for LzssParams {
+    fn from(preset: LzssPreset) -> Self {
+        preset.params()
+    }
+}
+
+/// Builder for [`LzssParams`], starting from the QuickBMS defaults
+#[derive(Debug, Clone)]
+pub struct LzssParamsBuilder {
+    params: LzssParams,
+}
+
+impl LzssParamsBuilder {
+    pub fn ei(mut self, ei: u32) -> Self {
+        self.params.ei = ei;
+        self
+    }
+
+    pub fn ej(mut self, ej: u32) -> Self {
+        self.params.ej = ej;
+        self
+    }
+
+    pub fn p(mut self, p: u32) -> Self {
+        self.params.p = p;
+        self
+    }
+
+    pub fn rless(mut self, rless: u32) -> Self {
+        self.params.rless = rless;
+        self
+    }
+
+    pub fn init_char(mut self, init_char: u8) -> Self {
+        self.params.init_char = init_char;
+        self
+    }
+
+    pub fn build(self) -> Result<LzssParams> {
+        let params = self.params;
+
+        // Positions and lengths share two bytes, the position gets 8 bits plus what ej leaves
+        if params.ei == 0 || params.ej == 0 || params.ej > 8 || params.ei + params.ej > 16 {
+            return Err(Error::Compression(
+                "ei and ej must fit in 16 bits".to_string(),
+            ));
+        }
+        if params.length_size() + params.p as usize >= params.window_size() {
+            return Err(Error::Compression(
+                "match length must be smaller than the window".to_string(),
+            ));
+        }
+        if params.rless as usize > params.window_size() - params.length_size() {
+            return Err(Error::Compression(
+                "rless must be inside the window".to_string(),
+            ));
+        }
+
+        Ok(params)
+    }
+}
+
+/// Decompress using the Acknex 3 parameters, returns the number of bytes decoded
+pub fn unlzss(src_data: &[u8], dest_data: &mut [u8]) -> Result<usize> {
+    unlzss_with(src_data, dest_data, &LzssParams::default())
+}
 
 // Note: The first few iterations are a direct translation of the C code, it will be refactored into idiomatic Rust later.
-pub fn unlzss(src_data: &[u8], dest_data: &mut [u8]) -> Result<(), &'static str> {
-    let ei = 12;
-    let ej = 4;
-    let p = 2;
-    let rless = p;
-    let init_char: u8 = 0x20;
+/// Decompress with the given parameters, returns the number of bytes decoded.
+///
+/// A stream decoding to less than `dest_data` leaves the rest of it untouched.
+pub fn unlzss_with(src_data: &[u8], dest_data: &mut [u8], params: &LzssParams) -> Result<usize> {
+    let ej = params.ej;
+    let p = params.p;
 
-    let mut n = 1 << ei;
-    let mut f = 1 << ej;
+    let mut n = params.window_size();
+    let mut f = params.length_size();
 
-    let mut slide_window = vec![init_char; n + f];
+    let mut slide_window = vec![params.init_char; n + f];
 
-    let mut r = (n - f) - rless;
+    let mut r = params.start_index();
     n -= 1;
     f -= 1;
 
@@ -39,7 +219,9 @@ pub fn unlzss(src_data: &[u8], dest_data: &mut [u8]) -> Result<(), &'static str>
             src_index += 1;
 
             if dest_index >= dest_data.len() {
-                return Err("Error: dest_index out of bounds");
+                return Err(Error::Compression(
+                    "Output is larger than the declared size".to_string(),
+                ));
             }
 
             dest_data[dest_index] = c as u8;
@@ -61,14 +243,16 @@ pub fn unlzss(src_data: &[u8], dest_data: &mut [u8]) -> Result<(), &'static str>
             src_index += 1;
 
             i |= (j >> ej) << 8;
-            j = (j & f as u32) + p as u32;
+            j = (j & f as u32) + p;
 
             for k in 0..=j {
                 let win_index = ((i + k) as usize) & n;
                 let c = slide_window[win_index] as u32;
 
                 if dest_index >= dest_data.len() {
-                    return Err("Error: dest_index out of bounds");
+                    return Err(Error::Compression(
+                        "Output is larger than the declared size".to_string(),
+                    ));
                 }
                 dest_data[dest_index] = c as u8;
                 dest_index += 1;
@@ -79,7 +263,285 @@ pub fn unlzss(src_data: &[u8], dest_data: &mut [u8]) -> Result<(), &'static str>
         }
     }
 
-    Ok(())
+    Ok(dest_index)
+}
+
+/// Compress using the Acknex 3 parameters
+pub fn lzss(src_data: &[u8]) -> Vec<u8> {
+    lzss_with(src_data, &LzssParams::default())
+}
+
+/// LZSS compressor producing streams which `unlzss` and the Acknex engine can read.
+///
+/// Matches are found with hash chains over the input history and emitted greedily.
+pub fn lzss_with(src_data: &[u8], params: &LzssParams) -> Vec<u8> {
+    let ej = params.ej;
+    let p = params.p as usize;
+
+    let n = params.window_size();
+    let f = params.length_size();
+
+    // Lengths are stored as (length - p - 1) in ej bits
+    let min_match = p + 1;
+    let max_match = f + p;
+
+    // Matches further back than this could be overwritten while the decoder copies them
+    let max_distance = n - max_match;
+
+    // Hash chains over the first bytes of each input position
+    let hash_len = min_match.min(3);
+    let hash_bits = 15;
+    let chain_limit = 256;
+    let hash = |data: &[u8]| {
+        data.iter().fold(0usize, |h, &b| (h << 5) ^ b as usize) & ((1 << hash_bits) - 1)
+    };
+    let mut head = vec![usize::MAX; 1 << hash_bits];
+    let mut prev = vec![usize::MAX; src_data.len()];
+
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + hash_len <= src_data.len() {
+            let h = hash(&src_data[pos..pos + hash_len]);
+            prev[pos] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    // The decoder starts writing at this window position
+    let r = params.start_index();
+
+    let mut dest_data = Vec::with_capacity(src_data.len() + src_data.len() / 8 + 1);
+    let mut flags_index = 0;
+    let mut flag_bit = 8;
+    let mut src_index = 0;
+
+    while src_index < src_data.len() {
+        if flag_bit == 8 {
+            flags_index = dest_data.len();
+            dest_data.push(0);
+            flag_bit = 0;
+        }
+
+        // Find the longest match in the history
+        let mut best_len = 0;
+        let mut best_pos = 0;
+        let limit = max_match.min(src_data.len() - src_index);
+
+        if limit >= min_match && src_index + hash_len <= src_data.len() {
+            let mut candidate = head[hash(&src_data[src_index..src_index + hash_len])];
+            let mut chain = 0;
+
+            while candidate != usize::MAX && chain < chain_limit {
+                if src_index - candidate > max_distance {
+                    break;
+                }
+
+                // Overlapping matches are fine, the decoder copies byte by byte
+                let len = (0..limit)
+                    .take_while(|&k| src_data[candidate + k] == src_data[src_index + k])
+                    .count();
+
+                if len > best_len {
+                    best_len = len;
+                    best_pos = candidate;
+                    if len == limit {
+                        break;
+                    }
+                }
+
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= min_match {
+            let pos = (r + best_pos) & (n - 1);
+            let len = best_len - min_match;
+
+            dest_data.push((pos & 0xff) as u8);
+            dest_data.push((((pos >> 8) << ej) | len) as u8);
+
+            for k in 0..best_len {
+                insert(src_index + k, &mut head, &mut prev);
+            }
+            src_index += best_len;
+        } else {
+            dest_data[flags_index] |= 1 << flag_bit;
+            dest_data.push(src_data[src_index]);
+
+            insert(src_index, &mut head, &mut prev);
+            src_index += 1;
+        }
+
+        flag_bit += 1;
+    }
+
+    dest_data
+}
+
+// Compressed bytes read from the inner reader at once
+const INPUT_BUFFER_SIZE: usize = 4096;
+
+/// Streaming LZSS decoder, decompresses from the inner reader as data is read.
+///
+/// The sliding window is kept between reads so output can be pulled in chunks of any size.
+/// Decoding stops at the end of the inner reader, wrap it in `take` to limit the output.
+/// Compressed data is read from the inner reader in chunks, so it may be read past what has
+/// been decoded.
+#[derive(Debug)]
+pub struct LzssReader<R> {
+    inner: R,
+    // Compressed bytes read from the inner reader but not decoded yet
+    input: Vec<u8>,
+    input_pos: usize,
+    input_len: usize,
+    slide_window: Vec<u8>,
+    ej: u32,
+    p: u32,
+    n: usize,
+    f: usize,
+    r: usize,
+    flags: u32,
+
+    // Remaining bytes of a match which didn't fit the last read
+    match_index: usize,
+    match_remaining: usize,
+
+    finished: bool,
+    truncated: bool,
+    consumed: u64,
+}
+
+impl<R: Read> LzssReader<R> {
+    /// Decompress using the Acknex 3 parameters
+    pub fn new(inner: R) -> Self {
+        Self::with_params(inner, &LzssParams::default())
+    }
+
+    pub fn with_params(inner: R, params: &LzssParams) -> Self {
+        let n = params.window_size();
+        let f = params.length_size();
+
+        Self {
+            inner,
+            input: vec![0; INPUT_BUFFER_SIZE],
+            input_pos: 0,
+            input_len: 0,
+            slide_window: vec![params.init_char; n + f],
+            ej: params.ej,
+            p: params.p,
+            n: n - 1,
+            f: f - 1,
+            r: params.start_index(),
+            flags: 0,
+            match_index: 0,
+            match_remaining: 0,
+            finished: false,
+            truncated: false,
+            consumed: 0,
+        }
+    }
+
+    /// The inner reader, compressed bytes read ahead but not decoded are lost
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Number of compressed bytes read from the inner reader so far
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// True if the compressed stream ended in the middle of a literal or match
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        while self.input_pos == self.input_len {
+            match self.inner.read(&mut self.input) {
+                Ok(0) => return Ok(None),
+                Ok(len) => {
+                    self.input_pos = 0;
+                    self.input_len = len;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let byte = self.input[self.input_pos];
+        self.input_pos += 1;
+        self.consumed += 1;
+        Ok(Some(byte))
+    }
+
+    fn push(&mut self, c: u8) {
+        self.slide_window[self.r] = c;
+        self.r = (self.r + 1) & self.n;
+    }
+
+    /// Decode the next literal or match, returns false at the end of the compressed stream
+    fn next_token(&mut self, out: &mut u8) -> io::Result<bool> {
+        self.flags >>= 1;
+
+        if self.flags & 0x100 == 0 {
+            let Some(flags) = self.read_byte()? else {
+                return Ok(false);
+            };
+            self.flags = flags as u32 | 0xff00;
+        }
+
+        if self.flags & 1 != 0 {
+            // Unused flag bits are 0, so a literal flag means its byte should follow
+            let Some(c) = self.read_byte()? else {
+                self.truncated = true;
+                return Ok(false);
+            };
+            *out = c;
+            self.push(c);
+        } else {
+            let Some(i) = self.read_byte()? else {
+                return Ok(false);
+            };
+            let Some(j) = self.read_byte()? else {
+                self.truncated = true;
+                return Ok(false);
+            };
+            let (i, j) = (i as u32, j as u32);
+
+            self.match_index = (i | ((j >> self.ej) << 8)) as usize;
+            self.match_remaining = ((j & self.f as u32) + self.p + 1) as usize;
+            *out = self.next_match_byte();
+        }
+
+        Ok(true)
+    }
+
+    fn next_match_byte(&mut self) -> u8 {
+        let c = self.slide_window[self.match_index & self.n];
+        self.match_index += 1;
+        self.match_remaining -= 1;
+        self.push(c);
+        c
+    }
+}
+
+impl<R: Read> Read for LzssReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+
+        while written < buf.len() {
+            if self.match_remaining > 0 {
+                buf[written] = self.next_match_byte();
+            } else if self.finished || !self.next_token(&mut buf[written])? {
+                self.finished = true;
+                break;
+            }
+            written += 1;
+        }
+
+        Ok(written)
+    }
 }
 
 #[cfg(test)]
@@ -87,7 +549,7 @@ mod tests {
     use super::*;
 
     // Known good compressed and decompressed data from an Acknex WRS archive
-    const COMPRESSED_DATA: &'static [u8] = &[
+    const COMPRESSED_DATA: &[u8] = &[
         223, 10, 5, 1, 8, 0, 242, 240, 35, 0, 63, 31, 0, 44, 1, 44, 1, 242, 241, 1, 15, 252, 19,
         15, 29, 6, 1, 36, 0, 1, 0, 128, 99, 2, 224, 253, 255, 72, 15, 90, 15, 0, 228, 109, 15, 24,
         127, 15, 145, 15, 155, 6, 12, 16, 175, 15, 193, 15, 211, 15, 0, 229, 15, 247, 15, 9, 31,
@@ -98,7 +560,7 @@ mod tests {
     ];
     const _COMPRESSED_SIZE: usize = 145;
 
-    const DECOMPRESSED_DATA: &'static [u8] = &[
+    const DECOMPRESSED_DATA: &[u8] = &[
         10, 5, 1, 8, 0, 0, 0, 0, 35, 0, 31, 0, 44, 1, 44, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
         0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
         0, 0, 0, 0, 0, 0, 1, 36, 0, 1, 0, 128, 2, 224, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -155,4 +617,118 @@ mod tests {
             Err(e) => panic!("Error: {}", e),
         }
     }
+
+    fn round_trip(data: &[u8]) {
+        let compressed = lzss(data);
+        let mut output = vec![0u8; data.len()];
+
+        unlzss(&compressed, &mut output).unwrap();
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn test_lzss() {
+        round_trip(&[]);
+        round_trip(b"A");
+        round_trip(b"ABABABABABABABABABABABABABABABAB");
+        round_trip(&[0u8; 10000]);
+        round_trip(DECOMPRESSED_DATA);
+
+        // Pseudo random data with some repetition
+        let mut seed: u32 = 12345;
+        let data: Vec<u8> = (0..20000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                ((seed >> 16) % 12) as u8
+            })
+            .collect();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn test_lzss_compresses() {
+        let compressed = lzss(DECOMPRESSED_DATA);
+        assert!(compressed.len() <= COMPRESSED_DATA.len() * 2);
+        assert!(lzss(&[16u8; 1000]).len() < 150);
+    }
+
+    #[test]
+    fn test_lzss_reader() {
+        let mut output = Vec::new();
+        LzssReader::new(COMPRESSED_DATA)
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, DECOMPRESSED_DATA);
+
+        // Small reads split matches across calls
+        let mut reader = LzssReader::new(COMPRESSED_DATA);
+        let mut output = Vec::new();
+        let mut chunk = [0u8; 5];
+        loop {
+            let len = reader.read(&mut chunk).unwrap();
+            if len == 0 {
+                break;
+            }
+            output.extend_from_slice(&chunk[..len]);
+        }
+        assert_eq!(output, DECOMPRESSED_DATA);
+        assert!(!reader.is_truncated());
+
+        // A literal flag with nothing after it is a cut short stream, not the end of one
+        let mut reader = LzssReader::new([0x01].as_slice());
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 0);
+        assert!(reader.is_truncated());
+        let mut reader = LzssReader::new([0x01, b'A'].as_slice());
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 1);
+        assert!(!reader.is_truncated());
+    }
+
+    #[test]
+    fn test_params() {
+        assert_eq!(
+            LzssParams::builder().build().unwrap(),
+            LzssParams::default()
+        );
+        assert_eq!(LzssPreset::Acknex3.params(), LzssParams::default());
+        assert!(LzssParams::builder().ei(14).ej(4).build().is_err());
+        assert!(LzssParams::builder().ei(4).ej(4).p(2).build().is_err());
+
+        let params = LzssParams::builder().ei(10).ej(6).rless(0).build().unwrap();
+        let data: Vec<u8> = (0..5000).map(|i| (i % 13 + i / 300) as u8).collect();
+        let compressed = lzss_with(&data, &params);
+
+        let mut output = vec![0u8; data.len()];
+        unlzss_with(&compressed, &mut output, &params).unwrap();
+        assert_eq!(output, data);
+
+        let mut output = Vec::new();
+        LzssReader::with_params(compressed.as_slice(), &params)
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, data);
+
+        // Streams packed with a smaller window only decode with the matching preset
+        let params = LzssPreset::SmallWindow.params();
+        assert_eq!(params.to_builder().build().unwrap(), params);
+        let compressed = lzss_with(&data, &params);
+        let mut output = vec![0u8; data.len()];
+        assert_eq!(
+            unlzss_with(&compressed, &mut output, &params).unwrap(),
+            data.len()
+        );
+        assert_eq!(output, data);
+        let mut output = vec![0u8; data.len()];
+        let _ = unlzss_with(&compressed, &mut output, &LzssParams::default());
+        assert_ne!(output, data);
+
+        // The window contents only matter for streams referencing it before it's written
+        let params = LzssPreset::Lzss0.params();
+        let mut output = vec![0u8; 4];
+        unlzss_with(&[0x00, 0x00, 0xf1], &mut output, &params).unwrap();
+        assert_eq!(output, [0, 0, 0, 0]);
+
+        // Presets can be tweaked, keeping what isn't overridden
+        let params = params.to_builder().ei(11).build().unwrap();
+        assert_eq!((params.ei(), params.init_char()), (11, 0));
+    }
 }
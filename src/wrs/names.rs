@@ -0,0 +1,93 @@
+use crate::{Error, Result};
+
+/// Device names which can't be used as file names on Windows
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turn a raw 13 byte name field into a file name which is safe to create on any platform.
+///
+/// Names which could escape the output directory are rejected, anything else that isn't
+/// printable ASCII or is reserved on Windows is replaced with `_`.
+pub fn sanitize_name(raw_name: &[u8]) -> Result<String> {
+    let end = raw_name
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(raw_name.len());
+    let raw_name = &raw_name[..end];
+    let lossy = String::from_utf8_lossy(raw_name);
+
+    // Separators, drive letters and dot names could all point outside the output directory
+    if raw_name.iter().any(|b| matches!(b, b'/' | b'\\' | b':'))
+        || raw_name == b"."
+        || raw_name == b".."
+    {
+        return Err(Error::UnsafeName(format!(
+            "{:?} escapes the output directory",
+            lossy
+        )));
+    }
+
+    let name: String = raw_name
+        .iter()
+        .map(|&b| match b {
+            b'<' | b'>' | b'"' | b'|' | b'?' | b'*' => '_',
+            b if b.is_ascii_graphic() || b == b' ' => b as char,
+            _ => '_',
+        })
+        .collect();
+
+    // Windows drops trailing dots and spaces, which would merge different names
+    let name = name.trim_end_matches(['.', ' ']).to_string();
+    if name.is_empty() {
+        return Err(Error::UnsafeName(format!("{:?} is empty", lossy)));
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        return Ok(format!("_{}", name));
+    }
+
+    Ok(name)
+}
+
+/// Key used to detect names which collide on case-insensitive file systems
+pub fn collision_key(name: &str) -> String {
+    name.to_ascii_uppercase()
+}
+
+/// Alternative name for the nth collision, in the style of DOS short names
+pub fn renamed(name: &str, n: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}~{}.{}", stem, n, extension),
+        None => format!("{}~{}", name, n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name(b"WALL.PCX\0\0\0\0\0").unwrap(), "WALL.PCX");
+        assert_eq!(sanitize_name(b"A\x01B\xffC.PCX").unwrap(), "A_B_C.PCX");
+        assert_eq!(sanitize_name(b"WHAT?.WAV").unwrap(), "WHAT_.WAV");
+        assert_eq!(sanitize_name(b"CON.WDL").unwrap(), "_CON.WDL");
+        assert_eq!(sanitize_name(b"NAME. ").unwrap(), "NAME");
+
+        assert!(matches!(sanitize_name(b"..\0"), Err(Error::UnsafeName(_))));
+        assert!(sanitize_name(b"../ETC").is_err());
+        assert!(sanitize_name(b"..\\WIN.INI").is_err());
+        assert!(sanitize_name(b"C:AUTOEXEC").is_err());
+        assert!(sanitize_name(b"\0").is_err());
+        assert!(sanitize_name(b"...").is_err());
+    }
+
+    #[test]
+    fn test_renamed() {
+        assert_eq!(renamed("WALL.PCX", 1), "WALL~1.PCX");
+        assert_eq!(renamed("README", 2), "README~2");
+    }
+}
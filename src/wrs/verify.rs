@@ -0,0 +1,283 @@
+use std::{
+    fmt,
+    io::{self, Read, Seek},
+};
+
+use super::archive::{WrsArchive, WrsEntry, ENTRY_HEADER_SIZE};
+use super::lzss::LzssReader;
+use crate::Result;
+
+/// Integrity of a single archive entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryReport {
+    entry: WrsEntry,
+    header_valid: bool,
+    missing_bytes: u64,
+    stream_consumed: bool,
+    decompressed_size: usize,
+    trailing_bytes: u64,
+}
+
+impl EntryReport {
+    pub fn entry(&self) -> &WrsEntry {
+        &self.entry
+    }
+
+    /// The name field is NUL terminated, NUL padded, printable ASCII
+    pub fn header_valid(&self) -> bool {
+        self.header_valid
+    }
+
+    /// Compressed bytes missing because the archive ends early
+    pub fn missing_bytes(&self) -> u64 {
+        self.missing_bytes
+    }
+
+    /// The compressed stream ended cleanly instead of in the middle of a match
+    pub fn stream_consumed(&self) -> bool {
+        self.stream_consumed
+    }
+
+    /// Bytes the stream actually decompressed to
+    pub fn decompressed_size(&self) -> usize {
+        self.decompressed_size
+    }
+
+    pub fn size_matches(&self) -> bool {
+        self.decompressed_size == self.entry.size()
+    }
+
+    /// Compressed bytes left over once the declared size was reached
+    pub fn trailing_bytes(&self) -> u64 {
+        self.trailing_bytes
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.header_valid
+            && self.missing_bytes == 0
+            && self.stream_consumed
+            && self.size_matches()
+            && self.trailing_bytes == 0
+    }
+}
+
+impl fmt::Display for EntryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<12} ", self.entry.name())?;
+
+        if self.is_ok() {
+            return write!(f, "OK");
+        }
+
+        let mut issues = Vec::new();
+        if !self.header_valid {
+            issues.push(format!("invalid name {:?}", self.entry.raw_name()));
+        }
+        if self.missing_bytes > 0 {
+            issues.push(format!("{} bytes missing", self.missing_bytes));
+        }
+        if !self.stream_consumed {
+            issues.push("stream ends inside a match".to_string());
+        }
+        if !self.size_matches() {
+            issues.push(format!(
+                "decompressed to {} of {} bytes",
+                self.decompressed_size,
+                self.entry.size()
+            ));
+        }
+        if self.trailing_bytes > 0 {
+            issues.push(format!("{} bytes of trailing data", self.trailing_bytes));
+        }
+
+        write!(f, "CORRUPT: {}", issues.join(", "))
+    }
+}
+
+/// Integrity of a whole archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    entries: Vec<EntryReport>,
+    archive_size: u64,
+    partial_header: u64,
+}
+
+impl VerifyReport {
+    pub fn entries(&self) -> &[EntryReport] {
+        &self.entries
+    }
+
+    pub fn archive_size(&self) -> u64 {
+        self.archive_size
+    }
+
+    /// Bytes of an entry header cut short by the end of the archive
+    pub fn partial_header(&self) -> u64 {
+        self.partial_header
+    }
+
+    pub fn corrupt_entries(&self) -> impl Iterator<Item = &EntryReport> {
+        self.entries.iter().filter(|e| !e.is_ok())
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.partial_header == 0 && self.entries.iter().all(EntryReport::is_ok)
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        if self.partial_header > 0 {
+            writeln!(
+                f,
+                "Archive is truncated, the last entry header has {} of {} bytes",
+                self.partial_header, ENTRY_HEADER_SIZE
+            )?;
+        }
+
+        let corrupt = self.corrupt_entries().count();
+        write!(
+            f,
+            "{} entries, {} corrupt: {}",
+            self.entries.len(),
+            corrupt,
+            if self.is_ok() { "OK" } else { "CORRUPT" }
+        )
+    }
+}
+
+/// Checks the name field the same way DOS would accept it
+fn valid_name(raw_name: &[u8; 13]) -> bool {
+    let Some(end) = raw_name.iter().position(|&b| b == 0) else {
+        return false;
+    };
+
+    end > 0
+        && raw_name[..end].iter().all(|b| b.is_ascii_graphic())
+        && raw_name[end..].iter().all(|&b| b == 0)
+}
+
+impl<R: Read + Seek> WrsArchive<R> {
+    /// Decompress every entry without writing anything and report on its integrity
+    pub fn verify(&mut self) -> Result<VerifyReport> {
+        let entries = self.entries().to_vec();
+        let archive_size = self.size();
+        let params = *self.params();
+
+        let mut reports = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let available = archive_size
+                .saturating_sub(entry.data_offset())
+                .min(entry.compressed_size() as u64);
+            let missing_bytes = entry.compressed_size() as u64 - available;
+
+            // Read what's there so a truncated entry is still checked as far as possible
+            let mut compressed_data = vec![0u8; available as usize];
+            self.reader_mut()
+                .seek(io::SeekFrom::Start(entry.data_offset()))?;
+            self.reader_mut().read_exact(&mut compressed_data)?;
+
+            let mut reader = LzssReader::with_params(compressed_data.as_slice(), &params);
+            let mut decompressed_size = io::copy(
+                &mut (&mut reader).take(entry.size() as u64),
+                &mut io::sink(),
+            )? as usize;
+
+            let trailing_bytes = if decompressed_size == entry.size() {
+                let consumed = reader.consumed();
+                decompressed_size += io::copy(&mut reader, &mut io::sink())? as usize;
+                available - consumed
+            } else {
+                0
+            };
+
+            reports.push(EntryReport {
+                header_valid: valid_name(entry.raw_name()),
+                missing_bytes,
+                stream_consumed: !reader.is_truncated(),
+                decompressed_size,
+                trailing_bytes,
+                entry,
+            });
+        }
+
+        Ok(VerifyReport {
+            entries: reports,
+            archive_size,
+            // Leftovers too short for a header are what remains of one
+            partial_header: self.trailing_bytes(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrs::WrsWriter;
+    use std::io::Cursor;
+
+    fn test_archive() -> Vec<u8> {
+        let mut writer = WrsWriter::new(Vec::new());
+        writer.add("WALL.PCX", &[7u8; 300]).unwrap();
+        writer.add("LEVEL.WMP", b"VERTEX 0 0 0").unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_verify_ok() {
+        let mut archive = WrsArchive::new(Cursor::new(test_archive())).unwrap();
+        let report = archive.verify().unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(report.entries().len(), 2);
+        assert_eq!(report.corrupt_entries().count(), 0);
+    }
+
+    #[test]
+    fn test_verify_corrupt() {
+        // Declare the first entry larger than its data
+        let mut data = test_archive();
+        data[20] += 1;
+        // Cut the last entry short
+        data.truncate(data.len() - 3);
+
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        let report = archive.verify().unwrap();
+        let entries = report.entries();
+
+        assert!(!report.is_ok());
+        assert!(entries[0].header_valid());
+        assert!(!entries[0].size_matches());
+        assert_eq!(entries[1].missing_bytes(), 3);
+        assert!(!entries[1].is_ok());
+
+        // Garbage after the compressed stream
+        let mut data = test_archive();
+        let zsize = u32::from_be_bytes(data[13..17].try_into().unwrap());
+        let name_bytes = data[..13].to_vec();
+        data.truncate(0);
+        data.extend_from_slice(&name_bytes);
+        data.extend_from_slice(&(zsize + 2).to_be_bytes());
+        data.extend_from_slice(&300u32.to_be_bytes());
+        data.extend_from_slice(&crate::wrs::lzss::lzss(&[7u8; 300]));
+        data.extend_from_slice(&[0xff, 1]);
+
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        let report = archive.verify().unwrap();
+        assert_eq!(report.entries()[0].trailing_bytes(), 2);
+        assert!(!report.is_ok());
+
+        // The start of another entry header
+        let mut data = test_archive();
+        data.extend_from_slice(b"SKY.PCX");
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        let report = archive.verify().unwrap();
+        assert_eq!(report.partial_header(), 7);
+        assert!(report.entries().iter().all(EntryReport::is_ok));
+        assert!(!report.is_ok());
+        assert!(report.to_string().contains("truncated"));
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::lzss::{lzss_with, LzssParams};
+use super::names::sanitize_name;
+use crate::{Error, Result};
+
+/// Longest entry name which fits the 13 byte name field with a terminating NUL
+pub const MAX_NAME_LEN: usize = 12;
+
+/// Builds a WRS archive entry by entry
+#[derive(Debug)]
+pub struct WrsWriter<W: Write> {
+    writer: W,
+    file_count: usize,
+    params: LzssParams,
+}
+
+impl WrsWriter<BufWriter<File>> {
+    /// Create a new archive on disk
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> WrsWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            file_count: 0,
+            params: LzssParams::default(),
+        }
+    }
+
+    /// Compress entries with different LZSS parameters
+    pub fn with_params(mut self, params: LzssParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Number of entries written so far
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    /// Compress and append a file to the archive
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        check_name(name)?;
+
+        let too_large = |_| Error::Archive(format!("Entry too large: {}", name));
+        let size = u32::try_from(data.len()).map_err(too_large)?;
+        let compressed_data = lzss_with(data, &self.params);
+        let compressed_size = u32::try_from(compressed_data.len()).map_err(too_large)?;
+
+        let mut name_bytes = [0u8; 13];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+
+        self.writer.write_all(&name_bytes)?;
+        self.writer.write_all(&compressed_size.to_be_bytes())?;
+        self.writer.write_all(&size.to_be_bytes())?;
+        self.writer.write_all(&compressed_data)?;
+        self.file_count += 1;
+
+        Ok(())
+    }
+
+    /// Append a file from disk, the entry is named after the file
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let name = file_name(path)?;
+
+        let data = fs::read(path)?;
+        self.add(name, &data)
+    }
+
+    /// Append every file in a directory, sorted by name. Subdirectories are ignored.
+    ///
+    /// Every name is checked before anything is written, so a bad one leaves no partial archive.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+
+        for file in &files {
+            check_name(file_name(file)?)?;
+        }
+        for file in files {
+            self.add_file(&file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the archive and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Entry names must fit the name field and extract under the same name
+fn check_name(name: &str) -> Result<()> {
+    let fits = !name.is_empty() && name.len() <= MAX_NAME_LEN && name.is_ascii();
+    if !fits || sanitize_name(name.as_bytes()).ok().as_deref() != Some(name) {
+        return Err(Error::Archive(format!("Invalid entry name: {:?}", name)));
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::Archive(format!("Invalid file name: {:?}", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrs::WrsArchive;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() {
+        let texture: Vec<u8> = (0..4000).map(|i| (i % 7) as u8).collect();
+
+        let mut writer = WrsWriter::new(Vec::new());
+        writer.add("WALL.PCX", &texture).unwrap();
+        writer.add("LEVEL.WDL", b"BMAP wall, <wall.pcx>;").unwrap();
+        writer.add("EMPTY", &[]).unwrap();
+        assert_eq!(writer.file_count(), 3);
+        let data = writer.finish().unwrap();
+
+        let mut archive = WrsArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.read("WALL.PCX").unwrap(), texture);
+        assert_eq!(
+            archive.read("LEVEL.WDL").unwrap(),
+            b"BMAP wall, <wall.pcx>;"
+        );
+        assert!(archive.read("EMPTY").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let mut writer = WrsWriter::new(Vec::new());
+        assert!(writer.add("", b"data").is_err());
+        assert!(writer.add("LONGFILENAME.PCX", b"data").is_err());
+        // Names the extractor would change or refuse
+        for name in ["../EVIL", "A:B", "WALL?.PCX", "CON.PCX", "TRAIL.", "NUL\0X"] {
+            assert!(writer.add(name, b"data").is_err(), "{:?}", name);
+        }
+        assert_eq!(writer.file_count(), 0);
+
+        // A directory with one bad name writes nothing at all
+        let dir = std::env::temp_dir().join(format!("a3conv_writer_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("A.PCX"), b"a").unwrap();
+        fs::write(dir.join("B?.PCX"), b"b").unwrap();
+        let mut writer = WrsWriter::new(Vec::new());
+        assert!(writer.add_dir(&dir).is_err());
+        assert_eq!(writer.file_count(), 0);
+        assert!(writer.finish().unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}